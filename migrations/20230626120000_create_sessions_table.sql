@@ -0,0 +1,8 @@
+CREATE TABLE sessions (
+    id TEXT NOT NULL,
+    PRIMARY KEY (id),
+    state JSONB NOT NULL,
+    expires_at timestamptz NOT NULL
+);
+
+CREATE INDEX sessions_expires_at_idx ON sessions (expires_at);
@@ -0,0 +1,15 @@
+CREATE TYPE header_pair AS (
+    name TEXT,
+    value BYTEA
+);
+
+-- The response columns are only filled in once the first request finishes processing
+CREATE TABLE idempotency (
+    user_id uuid NOT NULL REFERENCES users (id),
+    idempotency_key TEXT NOT NULL,
+    PRIMARY KEY (user_id, idempotency_key),
+    response_status_code SMALLINT,
+    response_headers header_pair[],
+    response_body BYTEA,
+    created_at timestamptz NOT NULL
+);
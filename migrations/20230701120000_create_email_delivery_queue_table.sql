@@ -0,0 +1,13 @@
+CREATE TABLE email_delivery_queue (
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    recipient TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    content TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    n_attempts INTEGER NOT NULL,
+    execute_after timestamptz NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+CREATE INDEX email_delivery_queue_execute_after_idx ON email_delivery_queue (execute_after);
@@ -0,0 +1 @@
+ALTER TYPE subscription_status ADD VALUE 'UNSUBSCRIBED';
@@ -0,0 +1 @@
+ALTER TABLE email_delivery_queue ADD COLUMN unsubscribe_url TEXT;
@@ -0,0 +1,8 @@
+ALTER TABLE subscription_tokens ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+ALTER TABLE subscription_tokens ADD COLUMN expires_at timestamptz NOT NULL DEFAULT now() + interval '1 day';
+ALTER TABLE subscription_tokens ADD COLUMN consumed_at timestamptz;
+
+ALTER TABLE subscription_tokens ALTER COLUMN created_at DROP DEFAULT;
+ALTER TABLE subscription_tokens ALTER COLUMN expires_at DROP DEFAULT;
+
+CREATE INDEX subscription_tokens_expires_at_idx ON subscription_tokens (expires_at);
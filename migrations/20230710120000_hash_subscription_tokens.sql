@@ -0,0 +1,3 @@
+-- Outstanding tokens keep working, since their links are looked up by the same digest
+ALTER TABLE subscription_tokens RENAME COLUMN id TO token_hash;
+UPDATE subscription_tokens SET token_hash = encode(sha256(convert_to(token_hash, 'UTF8')), 'hex');
@@ -0,0 +1,12 @@
+CREATE TABLE captured_emails (
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    recipient TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    content TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    unsubscribe_url TEXT,
+    created_at timestamptz NOT NULL
+);
+
+CREATE INDEX captured_emails_created_at_idx ON captured_emails (created_at);
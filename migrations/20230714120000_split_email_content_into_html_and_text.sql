@@ -0,0 +1,18 @@
+-- Emails are sent with an HTML part and a plain-text alternative
+ALTER TABLE email_delivery_queue RENAME COLUMN content TO html_content;
+-- Generated from the HTML content when NULL
+ALTER TABLE email_delivery_queue ADD COLUMN text_content TEXT;
+UPDATE email_delivery_queue
+SET text_content = html_content,
+    html_content = '<pre>' || replace(replace(replace(html_content, '&', '&amp;'), '<', '&lt;'), '>', '&gt;') || '</pre>'
+WHERE content_type <> 'text/html';
+ALTER TABLE email_delivery_queue DROP COLUMN content_type;
+
+ALTER TABLE captured_emails RENAME COLUMN content TO html_content;
+ALTER TABLE captured_emails ADD COLUMN text_content TEXT NOT NULL DEFAULT '';
+UPDATE captured_emails
+SET text_content = html_content,
+    html_content = '<pre>' || replace(replace(replace(html_content, '&', '&amp;'), '<', '&lt;'), '>', '&gt;') || '</pre>'
+WHERE content_type <> 'text/html';
+ALTER TABLE captured_emails ALTER COLUMN text_content DROP DEFAULT;
+ALTER TABLE captured_emails DROP COLUMN content_type;
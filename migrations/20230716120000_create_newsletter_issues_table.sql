@@ -0,0 +1,20 @@
+CREATE TABLE newsletter_issues (
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    subject TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    text_content TEXT,
+    published_at timestamptz NOT NULL
+);
+
+-- The deliveries of a newsletter issue take their content from it, to be sent in batches
+ALTER TABLE email_delivery_queue
+    ADD COLUMN newsletter_issue_id uuid REFERENCES newsletter_issues (id),
+    ALTER COLUMN subject DROP NOT NULL,
+    ALTER COLUMN html_content DROP NOT NULL,
+    ADD CONSTRAINT email_delivery_queue_content_check CHECK (
+        (newsletter_issue_id IS NULL) = (subject IS NOT NULL AND html_content IS NOT NULL)
+    );
+
+CREATE INDEX email_delivery_queue_newsletter_issue_id_idx
+    ON email_delivery_queue (newsletter_issue_id, execute_after);
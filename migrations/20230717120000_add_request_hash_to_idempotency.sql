@@ -0,0 +1,5 @@
+-- Tells a retry apart from a different request reusing the key, NULL for the rows saved before
+ALTER TABLE idempotency ADD COLUMN request_hash BYTEA;
+
+-- Rows are deleted once they are older than the retention
+CREATE INDEX idempotency_created_at_idx ON idempotency (created_at);
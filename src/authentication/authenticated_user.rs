@@ -0,0 +1,85 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use actix_web::dev::Payload;
+use actix_web::error::{ErrorInternalServerError, InternalError};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse};
+use anyhow::Context;
+use base64::Engine;
+use secrecy::Secret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::{validate_credentials, AuthError, Credentials, TypedSession};
+
+/// A user authenticated either through HTTP Basic credentials (for scripts) or through the
+/// session cookie set at login (for browsers).
+///
+/// Credentials in the `Authorization` header take precedence over the session.
+pub struct AuthenticatedUser {
+    pub user_id: Uuid,
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let pool = req.app_data::<web::Data<PgPool>>().cloned();
+        let credentials = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .map(basic_authentication);
+        let session = TypedSession::from_request(req, payload).into_inner();
+
+        Box::pin(async move {
+            if let Some(credentials) = credentials {
+                let credentials = credentials.map_err(unauthorized)?;
+                let pool = pool.ok_or_else(|| ErrorInternalServerError("Missing database pool"))?;
+
+                return match validate_credentials(credentials, &pool).await {
+                    Ok(user_id) => Ok(Self { user_id }),
+                    Err(AuthError::InvalidCredentials(e)) => Err(unauthorized(e)),
+                    Err(AuthError::UnexpectedError(e)) => Err(ErrorInternalServerError(e)),
+                };
+            }
+
+            match session?.get_user_id() {
+                Ok(Some(user_id)) => Ok(Self { user_id }),
+                Ok(None) => Err(unauthorized(anyhow::anyhow!("The user is not logged in."))),
+                Err(e) => Err(ErrorInternalServerError(e)),
+            }
+        })
+    }
+}
+
+fn unauthorized(e: anyhow::Error) -> actix_web::Error {
+    let response = HttpResponse::Unauthorized()
+        .insert_header((header::WWW_AUTHENTICATE, r#"Basic realm="publish""#))
+        .finish();
+
+    InternalError::from_response(e, response).into()
+}
+
+fn basic_authentication(header_value: &HeaderValue) -> Result<Credentials, anyhow::Error> {
+    let encoded_segment = header_value
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string.")?
+        .strip_prefix("Basic ")
+        .context("The authorization scheme was not 'Basic'.")?;
+    let decoded_bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded_segment)
+        .context("Failed to base64-decode 'Basic' credentials.")?;
+    let decoded_credentials = String::from_utf8(decoded_bytes)
+        .context("The decoded credential string is not valid UTF8.")?;
+
+    let (username, password) = decoded_credentials
+        .split_once(':')
+        .context("The 'Basic' credentials are missing a ':' separator.")?;
+
+    Ok(Credentials {
+        username: username.to_string(),
+        password: Secret::new(password.to_string()),
+    })
+}
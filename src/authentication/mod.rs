@@ -0,0 +1,7 @@
+pub use authenticated_user::*;
+pub use password::*;
+pub use session::*;
+
+mod authenticated_user;
+mod password;
+mod session;
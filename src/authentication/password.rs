@@ -0,0 +1,179 @@
+use anyhow::Context;
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub struct Credentials {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuthError {
+    #[error("Invalid credentials.")]
+    InvalidCredentials(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+/// Verified against when the username does not exist, so that a login attempt takes the same
+/// time whether or not the user is known. Hashed from a random password with the parameters of
+/// [`compute_password_hash`], so that it costs as much to verify.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=15000,t=2,p=1$WPGTYrhnggPAB+D9U6Bgeg$qUdSOKT3hDUBHd4ZumoQm4tdINP9EjnFSjPUJqkqWvc";
+
+#[tracing::instrument(name = "Validate credentials", skip_all, fields(username = %credentials.username))]
+pub async fn validate_credentials(
+    credentials: Credentials,
+    pool: &PgPool,
+) -> Result<Uuid, AuthError> {
+    let (user_id, expected_password_hash) =
+        match get_stored_credentials(&credentials.username, pool).await? {
+            Some((user_id, password_hash)) => (Some(user_id), password_hash),
+            None => (None, Secret::new(DUMMY_PASSWORD_HASH.to_string())),
+        };
+
+    spawn_blocking_with_tracing(move || {
+        verify_password_hash(expected_password_hash, credentials.password)
+    })
+    .await
+    .context("Failed to spawn blocking task")??;
+
+    user_id
+        .ok_or_else(|| anyhow::anyhow!("Unknown username."))
+        .map_err(AuthError::InvalidCredentials)
+}
+
+#[tracing::instrument(name = "Get stored credentials", skip_all)]
+async fn get_stored_credentials(
+    username: &str,
+    pool: &PgPool,
+) -> Result<Option<(Uuid, Secret<String>)>, anyhow::Error> {
+    let row = sqlx::query!(
+        "SELECT id, password_hash FROM users WHERE username = $1",
+        username
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch stored credentials")?;
+
+    Ok(row.map(|r| (r.id, Secret::new(r.password_hash))))
+}
+
+#[tracing::instrument(name = "Verify password hash", skip_all)]
+fn verify_password_hash(
+    expected_password_hash: Secret<String>,
+    password_candidate: Secret<String>,
+) -> Result<(), AuthError> {
+    let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
+        .context("Failed to parse hash in PHC string format")?;
+
+    Argon2::default()
+        .verify_password(
+            password_candidate.expose_secret().as_bytes(),
+            &expected_password_hash,
+        )
+        .context("Invalid password.")
+        .map_err(AuthError::InvalidCredentials)
+}
+
+/// Stores the user with the hash of their password, or updates the password of an existing one.
+#[tracing::instrument(name = "Store credentials", skip_all, fields(username = %credentials.username))]
+pub async fn store_credentials(
+    credentials: Credentials,
+    pool: &PgPool,
+) -> Result<Uuid, anyhow::Error> {
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(credentials.password))
+            .await
+            .context("Failed to spawn blocking task")?
+            .context("Failed to hash the password")?;
+
+    let user_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO users (id, username, password_hash)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (username) DO UPDATE SET password_hash = EXCLUDED.password_hash
+        RETURNING id
+        "#,
+        Uuid::new_v4(),
+        credentials.username,
+        password_hash.expose_secret(),
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to store credentials")?;
+
+    Ok(user_id)
+}
+
+pub fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(15000, 2, 1, None).unwrap(),
+    )
+    .hash_password(password.expose_secret().as_bytes(), &salt)?
+    .to_string();
+
+    Ok(Secret::new(password_hash))
+}
+
+fn spawn_blocking_with_tracing<F, R>(f: F) -> tokio::task::JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let current_span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || current_span.in_scope(f))
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    use super::*;
+
+    #[test]
+    fn a_password_matches_its_own_hash() {
+        let password = Secret::new("everything-has-to-start-somewhere".to_string());
+        let password_hash = compute_password_hash(password.clone()).unwrap();
+
+        assert_ok!(verify_password_hash(password_hash, password));
+    }
+
+    #[test]
+    fn a_different_password_does_not_match_the_hash() {
+        let password = Secret::new("everything-has-to-start-somewhere".to_string());
+        let password_hash = compute_password_hash(password).unwrap();
+
+        let result = verify_password_hash(password_hash, Secret::new("guess".to_string()));
+
+        assert!(matches!(
+            assert_err!(result),
+            AuthError::InvalidCredentials(_)
+        ));
+    }
+
+    #[test]
+    fn the_dummy_hash_costs_as_much_to_verify_as_the_others() {
+        let password_hash = compute_password_hash(Secret::new("password".to_string())).unwrap();
+        let password_hash = PasswordHash::new(password_hash.expose_secret()).unwrap();
+        let dummy_password_hash = PasswordHash::new(DUMMY_PASSWORD_HASH).unwrap();
+
+        assert_eq!(password_hash.algorithm, dummy_password_hash.algorithm);
+        assert_eq!(password_hash.version, dummy_password_hash.version);
+        assert_eq!(password_hash.params, dummy_password_hash.params);
+    }
+
+    #[test]
+    fn hashes_use_argon2id() {
+        let password_hash = compute_password_hash(Secret::new("password".to_string())).unwrap();
+
+        assert!(password_hash.expose_secret().starts_with("$argon2id$"));
+    }
+}
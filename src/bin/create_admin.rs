@@ -0,0 +1,45 @@
+//! Creates an administrator, or resets their password, e.g.
+//! `echo "$ADMIN_PASSWORD" | create-admin admin`.
+//!
+//! The password is read from the standard input so that it does not end up in the shell history.
+use std::io::BufRead;
+
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::postgres::PgPoolOptions;
+
+use zero2prod::authentication::{store_credentials, Credentials};
+use zero2prod::settings::SETTINGS;
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let username = std::env::args()
+        .nth(1)
+        .context("Usage: create-admin <username>, with the password on the standard input")?;
+
+    let mut password = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut password)
+        .context("Failed to read the password")?;
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    anyhow::ensure!(!password.is_empty(), "The password must not be empty");
+
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect_with(SETTINGS.database.with_db())
+        .await
+        .context("Failed to connect to Postgres")?;
+
+    let user_id = store_credentials(
+        Credentials {
+            username: username.clone(),
+            password: Secret::new(password),
+        },
+        &pool,
+    )
+    .await?;
+    println!("Stored the credentials of {username} ({user_id})");
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::settings::CleanupSettings;
+
+pub async fn run_cleanup_until_stopped(
+    pool: PgPool,
+    settings: CleanupSettings,
+) -> Result<(), anyhow::Error> {
+    let subscription_token_retention =
+        Duration::from_secs(settings.subscription_token_retention_secs);
+    let idempotency_key_retention = Duration::from_secs(settings.idempotency_key_retention_secs);
+
+    loop {
+        // Failures are logged and retried on the next run
+        let _ = delete_stale_subscription_tokens(&pool, subscription_token_retention).await;
+        let _ = delete_expired_sessions(&pool).await;
+        let _ = delete_stale_idempotency_keys(&pool, idempotency_key_retention).await;
+        tokio::time::sleep(Duration::from_secs(settings.interval_secs)).await;
+    }
+}
+
+/// Deletes the subscription tokens that expired more than `retention` ago, returning how many
+/// were deleted.
+#[tracing::instrument(name = "Deleting stale subscription tokens", skip_all, err)]
+pub async fn delete_stale_subscription_tokens(
+    pool: &PgPool,
+    retention: Duration,
+) -> Result<u64, anyhow::Error> {
+    let expired_before = chrono::Utc::now() - chrono::Duration::from_std(retention)?;
+
+    let result = sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE expires_at < $1",
+        expired_before
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete stale subscription tokens")?;
+
+    tracing::info!(
+        "Deleted {} stale subscription tokens",
+        result.rows_affected()
+    );
+
+    Ok(result.rows_affected())
+}
+
+/// Deletes the sessions that expired without logging out, returning how many were deleted.
+#[tracing::instrument(name = "Deleting expired sessions", skip_all, err)]
+pub async fn delete_expired_sessions(pool: &PgPool) -> Result<u64, anyhow::Error> {
+    let result = sqlx::query!("DELETE FROM sessions WHERE expires_at <= now()")
+        .execute(pool)
+        .await
+        .context("Failed to delete expired sessions")?;
+
+    tracing::info!("Deleted {} expired sessions", result.rows_affected());
+
+    Ok(result.rows_affected())
+}
+
+/// Deletes the idempotency keys saved more than `retention` ago, so that they can be used again,
+/// returning how many were deleted.
+#[tracing::instrument(name = "Deleting stale idempotency keys", skip_all, err)]
+pub async fn delete_stale_idempotency_keys(
+    pool: &PgPool,
+    retention: Duration,
+) -> Result<u64, anyhow::Error> {
+    let created_before = chrono::Utc::now() - chrono::Duration::from_std(retention)?;
+
+    let result = sqlx::query!(
+        "DELETE FROM idempotency WHERE created_at < $1",
+        created_before
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete stale idempotency keys")?;
+
+    tracing::info!("Deleted {} stale idempotency keys", result.rows_affected());
+
+    Ok(result.rows_affected())
+}
@@ -1,10 +1,32 @@
 #[derive(Clone, Debug, serde::Deserialize)]
 pub struct EmailAddress(String);
 
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum EmailAddressError {
+    #[error("email address cannot be empty")]
+    Blank,
+    #[error("invalid email address")]
+    Invalid,
+}
+
+impl EmailAddressError {
+    /// A stable identifier of the error, for clients to act upon.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Blank => "blank",
+            Self::Invalid => "invalid",
+        }
+    }
+}
+
 impl EmailAddress {
-    pub fn parse(raw_email: String) -> Result<Self, String> {
+    pub fn parse(raw_email: String) -> Result<Self, EmailAddressError> {
+        if raw_email.trim().is_empty() {
+            return Err(EmailAddressError::Blank);
+        }
+
         if !validator::validate_email(&raw_email) {
-            return Err("invalid email address".into());
+            return Err(EmailAddressError::Invalid);
         }
 
         Ok(Self(raw_email))
@@ -19,7 +41,7 @@ impl AsRef<str> for EmailAddress {
 
 #[cfg(test)]
 mod tests {
-    use claims::assert_err;
+    use claims::assert_err_eq;
     use fake::faker::internet::en::SafeEmail;
     use fake::Fake;
     use quickcheck::Gen;
@@ -41,13 +63,13 @@ mod tests {
     #[test]
     fn an_empty_string_is_rejected() {
         let email = "".to_string();
-        assert_err!(EmailAddress::parse(email));
+        assert_err_eq!(EmailAddress::parse(email), EmailAddressError::Blank);
     }
 
     #[test]
     fn an_email_without_at_symbol_is_rejected() {
         let email = "zouabi.com".to_string();
-        assert_err!(EmailAddress::parse(email));
+        assert_err_eq!(EmailAddress::parse(email), EmailAddressError::Invalid);
     }
 
     #[quickcheck_macros::quickcheck]
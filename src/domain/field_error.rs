@@ -0,0 +1,33 @@
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+
+/// Why the value of an input field is invalid.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct FieldError {
+    /// The path of the field, e.g. `name`
+    pub field: Cow<'static, str>,
+    /// A stable identifier of the error, e.g. `blank`
+    pub code: &'static str,
+    /// A human-readable description of the error
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(
+        field: impl Into<Cow<'static, str>>,
+        code: &'static str,
+        message: impl Display,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for FieldError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
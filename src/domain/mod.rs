@@ -1,9 +1,15 @@
 pub use email_address::*;
+pub use field_error::*;
 pub use personal_name::*;
 pub use subscriber::*;
 pub use subscription_status::*;
+pub use subscription_token::*;
+pub use unsubscribe_token::*;
 
 mod email_address;
+mod field_error;
 mod personal_name;
 mod subscriber;
 mod subscription_status;
+mod subscription_token;
+mod unsubscribe_token;
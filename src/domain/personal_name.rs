@@ -5,21 +5,42 @@ pub struct PersonalName(String);
 
 static FORBIDDEN_CHARS: [char; 10] = [';', '/', '(', ')', '"', '<', '>', '\\', '{', '}'];
 
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum PersonalNameError {
+    #[error("name cannot be empty")]
+    Blank,
+    #[error("name cannot be longer than 256 characters")]
+    TooLong,
+    #[error(
+        "name cannot contain the following special characters: {:?}",
+        FORBIDDEN_CHARS
+    )]
+    ForbiddenCharacters,
+}
+
+impl PersonalNameError {
+    /// A stable identifier of the error, for clients to act upon.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Blank => "blank",
+            Self::TooLong => "too_long",
+            Self::ForbiddenCharacters => "forbidden_characters",
+        }
+    }
+}
+
 impl PersonalName {
-    pub fn parse(raw_name: String) -> Result<Self, String> {
+    pub fn parse(raw_name: String) -> Result<Self, PersonalNameError> {
         if raw_name.trim().is_empty() {
-            return Err("name cannot be empty".into());
+            return Err(PersonalNameError::Blank);
         }
 
         if raw_name.graphemes(true).count() > 256 {
-            return Err("name cannot be longer than 256 characters".into());
+            return Err(PersonalNameError::TooLong);
         }
 
         if raw_name.chars().any(|char| FORBIDDEN_CHARS.contains(&char)) {
-            return Err(format!(
-                "name cannot contain the following special characters: {:?}",
-                &FORBIDDEN_CHARS
-            ));
+            return Err(PersonalNameError::ForbiddenCharacters);
         }
 
         Ok(Self(raw_name))
@@ -34,14 +55,14 @@ impl AsRef<str> for PersonalName {
 
 #[cfg(test)]
 mod tests {
-    use claims::{assert_err, assert_ok};
+    use claims::{assert_err_eq, assert_ok};
 
     use super::*;
 
     #[test]
     fn a_blank_name_is_rejected() {
         let name = " ".repeat(64);
-        assert_err!(PersonalName::parse(name));
+        assert_err_eq!(PersonalName::parse(name), PersonalNameError::Blank);
     }
 
     #[test]
@@ -53,14 +74,17 @@ mod tests {
     #[test]
     fn a_name_longer_than_256_graphemes_is_rejected() {
         let name = "مُحَمَّدَُ".repeat(65);
-        assert_err!(PersonalName::parse(name));
+        assert_err_eq!(PersonalName::parse(name), PersonalNameError::TooLong);
     }
 
     #[test]
     fn a_name_containing_forbidden_characters_is_rejected() {
         for &character in &FORBIDDEN_CHARS {
             let name = format!("Mohammad{}", character);
-            assert_err!(PersonalName::parse(name));
+            assert_err_eq!(
+                PersonalName::parse(name),
+                PersonalNameError::ForbiddenCharacters
+            );
         }
     }
 
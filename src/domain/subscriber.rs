@@ -1,24 +1,68 @@
-use crate::domain::{EmailAddress, PersonalName};
+use crate::domain::{EmailAddress, FieldError, PersonalName};
 
 #[derive(serde::Deserialize)]
 pub struct RawSubscriber {
+    /// Missing fields are blank, so that they are reported along with the other invalid fields
+    #[serde(default)]
     pub name: String,
+    #[serde(default)]
     pub email: String,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(Debug, serde::Deserialize)]
 pub struct Subscriber {
     pub name: PersonalName,
     pub email: EmailAddress,
 }
 
 impl TryFrom<RawSubscriber> for Subscriber {
-    type Error = String;
+    /// Every invalid field, not only the first one
+    type Error = Vec<FieldError>;
 
     fn try_from(raw_subscriber: RawSubscriber) -> Result<Self, Self::Error> {
-        let name = PersonalName::parse(raw_subscriber.name)?;
-        let email = EmailAddress::parse(raw_subscriber.email)?;
+        let name = PersonalName::parse(raw_subscriber.name)
+            .map_err(|e| FieldError::new("name", e.code(), e));
+        let email = EmailAddress::parse(raw_subscriber.email)
+            .map_err(|e| FieldError::new("email", e.code(), e));
+
+        match (name, email) {
+            (Ok(name), Ok(email)) => Ok(Self { name, email }),
+            (name, email) => Err(name.err().into_iter().chain(email.err()).collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use crate::domain::{FieldError, RawSubscriber, Subscriber};
+
+    #[test]
+    fn a_valid_subscriber_is_parsed_successfully() {
+        let raw_subscriber = RawSubscriber {
+            name: "Ursula Le Guin".into(),
+            email: "ursula_le_guin@gmail.com".into(),
+        };
+
+        assert_ok!(Subscriber::try_from(raw_subscriber));
+    }
+
+    #[test]
+    fn every_invalid_field_is_reported() {
+        let raw_subscriber = RawSubscriber {
+            name: "Robert'); DROP TABLE subscriptions;--".into(),
+            email: "".into(),
+        };
+
+        let errors = assert_err!(Subscriber::try_from(raw_subscriber));
 
-        Ok(Self { name, email })
+        assert_eq!(
+            vec![("name", "forbidden_characters"), ("email", "blank")],
+            errors
+                .iter()
+                .map(|FieldError { field, code, .. }| (field.as_ref(), *code))
+                .collect::<Vec<_>>()
+        );
     }
 }
@@ -3,4 +3,5 @@
 pub enum SubscriptionStatus {
     PendingConfirmation,
     Confirmed,
+    Unsubscribed,
 }
@@ -0,0 +1,62 @@
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sha2::{Digest, Sha256};
+
+/// A token sent to subscribers in confirmation links.
+///
+/// Only its digest is stored, so that reading the database is not enough to confirm a pending
+/// subscriber.
+pub struct SubscriptionToken(String);
+
+impl SubscriptionToken {
+    pub fn generate() -> Self {
+        let mut rng = thread_rng();
+
+        Self(
+            std::iter::repeat_with(|| rng.sample(Alphanumeric))
+                .map(char::from)
+                .take(25)
+                .collect(),
+        )
+    }
+
+    /// The hex-encoded SHA-256 digest of the token.
+    pub fn digest(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl From<String> for SubscriptionToken {
+    fn from(token: String) -> Self {
+        Self(token)
+    }
+}
+
+impl AsRef<str> for SubscriptionToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_tokens_are_25_alphanumeric_characters() {
+        let token = SubscriptionToken::generate();
+
+        assert_eq!(25, token.as_ref().len());
+        assert!(token.as_ref().chars().all(|c| c.is_ascii_alphanumeric()));
+    }
+
+    #[test]
+    fn the_digest_is_the_hex_encoded_sha256_of_the_token() {
+        let token = SubscriptionToken::from("abc".to_string());
+
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            token.digest()
+        );
+    }
+}
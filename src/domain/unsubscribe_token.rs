@@ -0,0 +1,103 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// A token identifying a subscriber in unsubscribe links.
+///
+/// It is the subscriber id followed by its HMAC-SHA256 tag, so it cannot be forged for another
+/// subscriber and does not need to be stored.
+pub struct UnsubscribeToken(String);
+
+impl UnsubscribeToken {
+    pub fn sign(subscriber_id: &Uuid, key: &Secret<String>) -> Self {
+        let mut bytes = subscriber_id.as_bytes().to_vec();
+        bytes.extend(mac(key, subscriber_id).finalize().into_bytes());
+
+        Self(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    /// Returns the id of the subscriber the token was signed for.
+    pub fn verify(raw_token: &str, key: &Secret<String>) -> Result<Uuid, String> {
+        let invalid = || "invalid unsubscribe token".to_string();
+
+        let bytes = URL_SAFE_NO_PAD.decode(raw_token).map_err(|_| invalid())?;
+        if bytes.len() <= 16 {
+            return Err(invalid());
+        }
+        let (id, tag) = bytes.split_at(16);
+        let subscriber_id = Uuid::from_slice(id).map_err(|_| invalid())?;
+
+        mac(key, &subscriber_id)
+            .verify_slice(tag)
+            .map_err(|_| invalid())?;
+
+        Ok(subscriber_id)
+    }
+}
+
+impl AsRef<str> for UnsubscribeToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+fn mac(key: &Secret<String>, subscriber_id: &Uuid) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(subscriber_id.as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok_eq};
+
+    use super::*;
+
+    fn key() -> Secret<String> {
+        Secret::new("a-secret-key".into())
+    }
+
+    #[test]
+    fn a_signed_token_is_verified_successfully() {
+        let subscriber_id = Uuid::new_v4();
+        let token = UnsubscribeToken::sign(&subscriber_id, &key());
+
+        assert_ok_eq!(
+            UnsubscribeToken::verify(token.as_ref(), &key()),
+            subscriber_id
+        );
+    }
+
+    #[test]
+    fn a_token_signed_with_another_key_is_rejected() {
+        let token = UnsubscribeToken::sign(&Uuid::new_v4(), &Secret::new("another-key".into()));
+
+        assert_err!(UnsubscribeToken::verify(token.as_ref(), &key()));
+    }
+
+    #[test]
+    fn a_token_for_another_subscriber_is_rejected() {
+        let token = UnsubscribeToken::sign(&Uuid::new_v4(), &key());
+        let mut bytes = URL_SAFE_NO_PAD.decode(token.as_ref()).unwrap();
+        bytes[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+
+        assert_err!(UnsubscribeToken::verify(
+            &URL_SAFE_NO_PAD.encode(bytes),
+            &key()
+        ));
+    }
+
+    #[test]
+    fn a_malformed_token_is_rejected() {
+        assert_err!(UnsubscribeToken::verify("", &key()));
+        assert_err!(UnsubscribeToken::verify("not base64!", &key()));
+        assert_err!(UnsubscribeToken::verify(
+            &URL_SAFE_NO_PAD.encode(Uuid::new_v4().as_bytes()),
+            &key()
+        ));
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::email::{EmailData, EmailSender, SendError};
+
+/// Stores emails in Postgres instead of sending them, so that they can be read in the
+/// development mailbox.
+pub struct CaptureClient {
+    pool: PgPool,
+}
+
+impl CaptureClient {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for CaptureClient {
+    async fn send(&self, email: &EmailData) -> Result<(), SendError> {
+        let text_content = email.plain_text();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO captured_emails (
+                id, recipient, subject, html_content, text_content, unsubscribe_url,
+                created_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, now())
+            "#,
+            Uuid::new_v4(),
+            email.to.as_ref(),
+            email.subject,
+            email.html_content,
+            text_content.as_ref(),
+            email.unsubscribe_url.as_ref().map(|url| url.as_str()),
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to capture the email")
+        .map_err(SendError::Transient)?;
+
+        Ok(())
+    }
+}
@@ -1,8 +1,251 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
 use crate::domain::EmailAddress;
+use crate::email::html_to_text;
+use crate::html::escape_html;
 
 pub struct EmailData {
     pub to: EmailAddress,
     pub subject: String,
-    pub content: String,
-    pub content_type: String,
+    pub html_content: String,
+    /// The plain-text alternative, generated from the HTML content when absent
+    pub text_content: Option<String>,
+    /// Added as `List-Unsubscribe` headers when present, the templates' layout adds the footer
+    pub unsubscribe_url: Option<reqwest::Url>,
+}
+
+impl EmailData {
+    /// The plain-text content, generated from the HTML content if absent.
+    pub fn plain_text(&self) -> Cow<'_, str> {
+        plain_text(&self.html_content, &self.text_content)
+    }
+
+    /// The one-click unsubscribe headers (RFC 8058), if any.
+    pub fn unsubscribe_headers(&self) -> Vec<(&'static str, String)> {
+        unsubscribe_headers(self.unsubscribe_url.as_ref())
+    }
+}
+
+/// The same email sent to many recipients, none of which can see the others.
+pub struct BatchEmailData {
+    pub subject: String,
+    pub html_content: String,
+    /// The plain-text alternative, generated from the HTML content when absent
+    pub text_content: Option<String>,
+    pub recipients: Vec<BatchRecipient>,
+}
+
+#[derive(Clone)]
+pub struct BatchRecipient {
+    pub to: EmailAddress,
+    /// Replaced in the subject and the content, e.g. `-name-` by the recipient's name, escaped in
+    /// the HTML content
+    pub substitutions: HashMap<String, String>,
+    /// Added as `List-Unsubscribe` headers when present
+    pub unsubscribe_url: Option<reqwest::Url>,
+}
+
+impl BatchEmailData {
+    /// The plain-text content, generated from the HTML content if absent.
+    pub fn plain_text(&self) -> Cow<'_, str> {
+        plain_text(&self.html_content, &self.text_content)
+    }
+
+    /// The same email split into batches of up to `size` recipients, in order.
+    pub fn chunks(&self, size: usize) -> impl Iterator<Item = BatchEmailData> + '_ {
+        self.recipients
+            .chunks(size)
+            .map(|recipients| BatchEmailData {
+                subject: self.subject.clone(),
+                html_content: self.html_content.clone(),
+                text_content: self.text_content.clone(),
+                recipients: recipients.to_vec(),
+            })
+    }
+
+    /// The keys substituted for any of the recipients, longest first.
+    pub fn substitution_keys(&self) -> Vec<&str> {
+        let mut keys = self
+            .recipients
+            .iter()
+            .flat_map(|recipient| recipient.substitutions.keys().map(String::as_str))
+            .filter(|key| !key.is_empty())
+            .collect::<Vec<_>>();
+        keys.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        keys.dedup();
+        keys
+    }
+
+    /// The email to each recipient, with their substitutions applied.
+    pub fn personalized(&self) -> impl Iterator<Item = EmailData> + '_ {
+        let keys = self.substitution_keys();
+
+        self.recipients.iter().map(move |recipient| {
+            let substitute = |content: &str| {
+                replace_keys(content, &keys, |_, key| {
+                    recipient
+                        .substitutions
+                        .get(key)
+                        .map_or(key, String::as_str)
+                        .to_string()
+                })
+            };
+            let substitute_html = |content: &str| {
+                replace_keys(content, &keys, |_, key| {
+                    recipient
+                        .substitutions
+                        .get(key)
+                        .map_or_else(|| key.to_string(), |value| escape_html(value))
+                })
+            };
+
+            EmailData {
+                to: recipient.to.clone(),
+                subject: substitute(&self.subject),
+                html_content: substitute_html(&self.html_content),
+                text_content: self.text_content.as_deref().map(substitute),
+                unsubscribe_url: recipient.unsubscribe_url.clone(),
+            }
+        })
+    }
+}
+
+impl BatchRecipient {
+    /// The one-click unsubscribe headers (RFC 8058), if any.
+    pub fn unsubscribe_headers(&self) -> Vec<(&'static str, String)> {
+        unsubscribe_headers(self.unsubscribe_url.as_ref())
+    }
+}
+
+/// Replaces the keys in a single pass, so that the replacements are not substituted again, trying
+/// the keys in their order at every position. The replacement is given the key's index.
+pub(crate) fn replace_keys(
+    content: &str,
+    keys: &[&str],
+    mut replacement: impl FnMut(usize, &str) -> String,
+) -> String {
+    let mut replaced = String::with_capacity(content.len());
+    let mut rest = content;
+
+    'rest: while let Some(c) = rest.chars().next() {
+        for (i, key) in keys.iter().enumerate() {
+            if !key.is_empty() && rest.starts_with(key) {
+                replaced.push_str(&replacement(i, key));
+                rest = &rest[key.len()..];
+                continue 'rest;
+            }
+        }
+
+        replaced.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    replaced
+}
+
+fn plain_text<'a>(html_content: &str, text_content: &'a Option<String>) -> Cow<'a, str> {
+    match text_content {
+        Some(text) => Cow::from(text),
+        None => Cow::from(html_to_text(html_content)),
+    }
+}
+
+fn unsubscribe_headers(unsubscribe_url: Option<&reqwest::Url>) -> Vec<(&'static str, String)> {
+    unsubscribe_url
+        .map(|url| {
+            vec![
+                ("List-Unsubscribe", format!("<{url}>")),
+                ("List-Unsubscribe-Post", "List-Unsubscribe=One-Click".into()),
+            ]
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::domain::EmailAddress;
+    use crate::email::{BatchEmailData, BatchRecipient};
+
+    fn batch(content: &str, substitutions: &[(&str, &str)]) -> BatchEmailData {
+        BatchEmailData {
+            subject: content.into(),
+            html_content: content.into(),
+            text_content: Some(content.into()),
+            recipients: vec![BatchRecipient {
+                to: EmailAddress::parse("subscriber@example.com".into()).unwrap(),
+                substitutions: substitutions
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect::<HashMap<_, _>>(),
+                unsubscribe_url: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn substitutions_are_escaped_in_the_html_content_only() {
+        // Given
+        let batch = batch("Hello -name-", &[("-name-", "<b>Tom & Jerry</b>")]);
+
+        // When
+        let email = batch.personalized().next().unwrap();
+
+        // Then
+        assert_eq!("Hello <b>Tom & Jerry</b>", email.subject);
+        assert_eq!("Hello <b>Tom & Jerry</b>", email.text_content.unwrap());
+        assert_eq!(
+            "Hello &lt;b&gt;Tom &amp; Jerry&lt;/b&gt;",
+            email.html_content
+        );
+    }
+
+    #[test]
+    fn overlapping_keys_are_substituted_longest_first() {
+        for _ in 0..10 {
+            // Given
+            let batch = batch(
+                "-name- -name-full-",
+                &[("-name-", "Tom"), ("-name-full-", "Tom Cat")],
+            );
+
+            // When
+            let email = batch.personalized().next().unwrap();
+
+            // Then
+            assert_eq!("Tom Tom Cat", email.subject);
+        }
+    }
+
+    #[test]
+    fn substituted_values_are_not_substituted_again() {
+        // Given
+        let batch = batch("-a- -b-", &[("-a-", "-b-"), ("-b-", "-a-")]);
+
+        // When
+        let email = batch.personalized().next().unwrap();
+
+        // Then
+        assert_eq!("-b- -a-", email.subject);
+    }
+
+    #[test]
+    fn keys_missing_for_a_recipient_are_left_as_they_are() {
+        // Given
+        let mut batch = batch("Hello -name-", &[("-name-", "Tom")]);
+        batch.recipients.push(BatchRecipient {
+            to: EmailAddress::parse("other@example.com".into()).unwrap(),
+            substitutions: HashMap::new(),
+            unsubscribe_url: None,
+        });
+
+        // When
+        let emails = batch.personalized().collect::<Vec<_>>();
+
+        // Then
+        assert_eq!("Hello Tom", emails[0].subject);
+        assert_eq!("Hello -name-", emails[1].subject);
+    }
 }
@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+
+/// Why an email could not be sent, so that callers can decide whether to retry it, to stop
+/// emailing the recipient or to alert.
+#[derive(thiserror::Error, Debug)]
+pub enum SendError {
+    /// The recipient's address does not exist or is suppressed by the provider
+    #[error("The recipient was rejected: {0}")]
+    InvalidRecipient(String),
+    /// The credentials were rejected, which only an operator can fix
+    #[error("The credentials were rejected: {0}")]
+    AuthFailure(String),
+    #[error("The provider is rate limiting: {message}")]
+    RateLimited {
+        /// How long the provider asked to wait before trying again
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    /// Trying again later may succeed, e.g. after a timeout or a server error
+    #[error("Transient failure: {0:#}")]
+    Transient(anyhow::Error),
+    /// The provider kept failing, so the email was not even attempted until the circuit closes
+    #[error("The email provider keeps failing, not sending emails for {retry_after:?}")]
+    CircuitOpen { retry_after: Duration },
+    /// Trying again will fail the same way, e.g. after the email was malformed
+    #[error("Permanent failure: {0:#}")]
+    Permanent(anyhow::Error),
+}
+
+impl SendError {
+    /// Classifies an error response of a provider's API by its status.
+    pub fn from_status(
+        provider: &str,
+        status: StatusCode,
+        retry_after: Option<Duration>,
+        details: &str,
+    ) -> Self {
+        let message = format!("{provider} responded with {status}, {details}");
+
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited {
+                retry_after,
+                message,
+            },
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Self::AuthFailure(message),
+            status if status.is_server_error() => Self::Transient(anyhow::anyhow!(message)),
+            _ => Self::Permanent(anyhow::anyhow!(message)),
+        }
+    }
+
+    /// A label of the variant, e.g. for metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidRecipient(_) => "invalid_recipient",
+            Self::AuthFailure(_) => "auth_failure",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::Transient(_) => "transient",
+            Self::CircuitOpen { .. } => "circuit_open",
+            Self::Permanent(_) => "permanent",
+        }
+    }
+
+    /// Whether trying again later may succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited { .. } | Self::Transient(_) | Self::CircuitOpen { .. }
+        )
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } => *retry_after,
+            Self::CircuitOpen { retry_after } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for SendError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() || e.is_connect() || e.is_request() {
+            Self::Transient(e.into())
+        } else {
+            Self::Permanent(e.into())
+        }
+    }
+}
+
+/// Parses the `Retry-After` header, given either in seconds or as an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use claims::{assert_matches, assert_none, assert_some_eq};
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use reqwest::StatusCode;
+
+    use crate::email::{retry_after, SendError};
+
+    fn headers(retry_after: &str) -> HeaderMap {
+        HeaderMap::from_iter([(RETRY_AFTER, HeaderValue::from_str(retry_after).unwrap())])
+    }
+
+    #[test]
+    fn retry_after_in_seconds_is_parsed() {
+        assert_some_eq!(retry_after(&headers("120")), Duration::from_secs(120));
+    }
+
+    #[test]
+    fn retry_after_as_an_http_date_is_parsed() {
+        let date = chrono::Utc::now() + chrono::Duration::seconds(60);
+
+        let parsed = retry_after(&headers(&date.to_rfc2822())).unwrap();
+
+        assert!(parsed > Duration::from_secs(55) && parsed <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn retry_after_in_the_past_is_zero() {
+        assert_some_eq!(
+            retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn invalid_or_missing_retry_after_is_ignored() {
+        assert_none!(retry_after(&headers("soon")));
+        assert_none!(retry_after(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn error_statuses_are_classified() {
+        let classify = |status| SendError::from_status("Provider", status, None, "details");
+
+        assert_matches!(
+            classify(StatusCode::TOO_MANY_REQUESTS),
+            SendError::RateLimited { .. }
+        );
+        assert_matches!(
+            classify(StatusCode::UNAUTHORIZED),
+            SendError::AuthFailure(_)
+        );
+        assert_matches!(classify(StatusCode::FORBIDDEN), SendError::AuthFailure(_));
+        assert_matches!(classify(StatusCode::BAD_GATEWAY), SendError::Transient(_));
+        assert_matches!(classify(StatusCode::BAD_REQUEST), SendError::Permanent(_));
+    }
+}
@@ -0,0 +1,186 @@
+/// Renders HTML as plain text, for the plain-text alternative of emails written in HTML.
+///
+/// Block elements and line breaks become new lines, and links are followed by their target.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut hrefs: Vec<Option<String>> = vec![];
+    let mut link_text_start = 0;
+    let mut skipped_element: Option<String> = None;
+    let mut chars = html.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '<' {
+            let mut tag = String::new();
+            for c in chars.by_ref() {
+                if c == '>' {
+                    break;
+                }
+                tag.push(c);
+            }
+            let tag = tag.trim();
+            let is_closing = tag.starts_with('/');
+            let name = tag
+                .trim_start_matches('/')
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or_default()
+                .to_lowercase();
+
+            if let Some(skipped) = &skipped_element {
+                if is_closing && &name == skipped {
+                    skipped_element = None;
+                }
+                continue;
+            }
+
+            match (name.as_str(), is_closing) {
+                ("script" | "style" | "head" | "title", false) => skipped_element = Some(name),
+                ("br", _) => text.push('\n'),
+                ("li", false) => text.push_str("\n- "),
+                ("a", false) => {
+                    hrefs.push(attribute(tag, "href"));
+                    link_text_start = text.len();
+                }
+                ("a", true) => {
+                    if let Some(Some(href)) = hrefs.pop() {
+                        if text[link_text_start..].trim() != href {
+                            text.push_str(&format!(" ({href})"));
+                        }
+                    }
+                }
+                (
+                    "p" | "div" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "ul" | "ol" | "table"
+                    | "tr" | "blockquote" | "hr",
+                    _,
+                ) => text.push_str("\n\n"),
+                _ => {}
+            }
+        } else if skipped_element.is_some() {
+            continue;
+        } else if c == '&' {
+            let mut entity = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == ';' || entity.len() > 8 {
+                    break;
+                }
+                entity.push(c);
+                chars.next();
+            }
+            if chars.next_if_eq(&';').is_some() {
+                text.push_str(&decode_entity(&entity).unwrap_or_else(|| format!("&{entity};")));
+            } else {
+                text.push('&');
+                text.push_str(&entity);
+            }
+        } else if c.is_whitespace() {
+            // Whitespace is collapsed as in HTML
+            if !text.ends_with(char::is_whitespace) {
+                text.push(' ');
+            }
+        } else {
+            text.push(c);
+        }
+    }
+
+    normalize_lines(&text)
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let start = tag.find(&format!("{name}="))? + name.len() + 1;
+    let value = &tag[start..];
+
+    let value = match value.chars().next()? {
+        quote @ ('"' | '\'') => value[1..].split(quote).next()?,
+        _ => value.split(char::is_whitespace).next()?,
+    };
+
+    Some(value.replace("&amp;", "&"))
+}
+
+fn decode_entity(entity: &str) -> Option<String> {
+    let decoded = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        _ => {
+            let code = match entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => entity.strip_prefix('#')?.parse().ok()?,
+            };
+            char::from_u32(code)?
+        }
+    };
+
+    Some(decoded.to_string())
+}
+
+/// Trims the lines and keeps at most one empty line between paragraphs.
+pub(crate) fn normalize_lines(text: &str) -> String {
+    let mut normalized = String::new();
+    let mut n_empty_lines = 0;
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            n_empty_lines += 1;
+            continue;
+        }
+        if !normalized.is_empty() {
+            normalized.push_str(if n_empty_lines > 0 { "\n\n" } else { "\n" });
+        }
+        normalized.push_str(line);
+        n_empty_lines = 0;
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::email::html_to_text;
+
+    #[test]
+    fn line_breaks_and_paragraphs_become_new_lines() {
+        let html = "<p>First   paragraph,<br>second\nline</p><p>Second paragraph</p>";
+
+        assert_eq!(
+            "First paragraph,\nsecond line\n\nSecond paragraph",
+            html_to_text(html)
+        );
+    }
+
+    #[test]
+    fn links_are_followed_by_their_target() {
+        let html = r#"You may <a href="https://example.com/confirm?a=1&amp;b=2">confirm</a>, or
+<a href="https://example.com">https://example.com</a>"#;
+
+        assert_eq!(
+            "You may confirm (https://example.com/confirm?a=1&b=2), or https://example.com",
+            html_to_text(html)
+        );
+    }
+
+    #[test]
+    fn entities_are_decoded() {
+        assert_eq!(
+            r#"<Tom> & "Jerry" — it's ok"#,
+            html_to_text("&lt;Tom&gt; &amp; &quot;Jerry&quot; &#8212; it&#x27;s ok")
+        );
+    }
+
+    #[test]
+    fn list_items_are_bulleted() {
+        let html = "<ul><li>One</li><li>Two</li></ul>";
+
+        assert_eq!("- One\n- Two", html_to_text(html));
+    }
+
+    #[test]
+    fn scripts_and_styles_are_dropped() {
+        let html = "<style>p { color: red; }</style><p>Hello</p><script>alert(1)</script>";
+
+        assert_eq!("Hello", html_to_text(html));
+    }
+}
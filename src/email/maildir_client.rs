@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use uuid::Uuid;
+
+use crate::domain::EmailAddress;
+use crate::email::mime::to_message;
+use crate::email::{EmailData, EmailSender, SendError};
+use crate::settings::EmailClientSettings;
+
+/// Writes emails as `.eml` files into a Maildir, so that they can be opened in a mail client or
+/// replayed later.
+pub struct MaildirClient {
+    path: PathBuf,
+    from: EmailAddress,
+}
+
+impl MaildirClient {
+    pub fn new(path: PathBuf, from: EmailAddress) -> Self {
+        Self { path, from }
+    }
+
+    pub fn from_settings(settings: &EmailClientSettings) -> Result<Self, anyhow::Error> {
+        let maildir = settings
+            .maildir
+            .as_ref()
+            .context("Maildir settings are required by the maildir provider")?;
+
+        Ok(Self::new(
+            PathBuf::from(&maildir.path),
+            settings.sender.clone(),
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for MaildirClient {
+    async fn send(&self, email: &EmailData) -> Result<(), SendError> {
+        let message = to_message(&self.from, email).map_err(SendError::Permanent)?;
+
+        for dir in ["tmp", "new", "cur"] {
+            tokio::fs::create_dir_all(self.path.join(dir))
+                .await
+                .context("Failed to create the Maildir")
+                .map_err(SendError::Transient)?;
+        }
+
+        // Written to `tmp` first and then moved to `new`, so that readers never see partial files
+        let file_name = format!(
+            "{}.{}.zero2prod.eml",
+            chrono::Utc::now().timestamp(),
+            Uuid::new_v4()
+        );
+        let tmp_path = self.path.join("tmp").join(&file_name);
+        tokio::fs::write(&tmp_path, message.formatted())
+            .await
+            .context("Failed to write the email")
+            .map_err(SendError::Transient)?;
+        tokio::fs::rename(&tmp_path, self.path.join("new").join(&file_name))
+            .await
+            .context("Failed to deliver the email to the Maildir")
+            .map_err(SendError::Transient)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use claims::assert_ok;
+    use fake::faker::internet::en::SafeEmail;
+    use fake::Fake;
+    use uuid::Uuid;
+
+    use crate::domain::EmailAddress;
+    use crate::email::test_helpers::fake_email_data;
+    use crate::email::{EmailData, EmailSender, MaildirClient};
+
+    /// A Maildir in the temporary directory, removed when dropped.
+    struct TestMaildir(PathBuf);
+
+    impl TestMaildir {
+        fn new() -> Self {
+            Self(std::env::temp_dir().join(format!("zero2prod-maildir-{}", Uuid::new_v4())))
+        }
+
+        fn files(&self, dir: &str) -> Vec<PathBuf> {
+            std::fs::read_dir(self.0.join(dir))
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect()
+        }
+    }
+
+    impl Drop for TestMaildir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn email_client(path: &Path) -> MaildirClient {
+        MaildirClient::new(
+            path.to_path_buf(),
+            EmailAddress::parse(SafeEmail().fake()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn send_email_writes_an_eml_file_to_the_new_directory() {
+        // Given
+        let maildir = TestMaildir::new();
+        let email_client = email_client(&maildir.0);
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_ok!(result);
+
+        let new_files = maildir.files("new");
+        assert_eq!(1, new_files.len());
+        assert_eq!("eml", new_files[0].extension().unwrap());
+        assert!(maildir.files("tmp").is_empty());
+        assert!(maildir.files("cur").is_empty());
+    }
+
+    #[tokio::test]
+    async fn the_eml_file_is_an_rfc_5322_message() {
+        // Given
+        let maildir = TestMaildir::new();
+        let email_client = email_client(&maildir.0);
+        let unsubscribe_url = reqwest::Url::parse("https://example.com/unsubscribe").unwrap();
+        let email = EmailData {
+            unsubscribe_url: Some(unsubscribe_url.clone()),
+            ..fake_email_data()
+        };
+
+        // When
+        email_client.send(&email).await.unwrap();
+
+        // Then
+        let eml = std::fs::read_to_string(&maildir.files("new")[0]).unwrap();
+        for header in [
+            "From: ",
+            "Date: ",
+            "Message-ID: <",
+            "MIME-Version: 1.0",
+            "Content-Type: multipart/alternative;",
+        ] {
+            assert!(eml.contains(header), "missing {header}");
+        }
+        assert!(eml.contains(&format!("To: {}", email.to.as_ref())));
+        assert!(eml.contains(&format!("Subject: {}", email.subject)));
+        assert!(eml.contains(&format!("List-Unsubscribe: <{unsubscribe_url}>")));
+        assert!(eml.contains("Content-Type: text/html"));
+    }
+
+    #[tokio::test]
+    async fn every_email_gets_its_own_file() {
+        // Given
+        let maildir = TestMaildir::new();
+        let email_client = email_client(&maildir.0);
+
+        // When
+        email_client.send(&fake_email_data()).await.unwrap();
+        email_client.send(&fake_email_data()).await.unwrap();
+
+        // Then
+        assert_eq!(2, maildir.files("new").len());
+    }
+}
@@ -0,0 +1,41 @@
+use std::borrow::Cow;
+
+pub static API_USERNAME: &str = "api";
+/// The start of the message of the requests rejected for their recipient
+pub static INVALID_RECIPIENT_MESSAGE: &str = "'to' parameter is not a valid address";
+
+pub fn send_path(domain: &str) -> String {
+    format!("/v3/{domain}/messages")
+}
+
+/// Sent form-encoded.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SendMessageBody<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+    pub subject: &'a str,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<Cow<'a, str>>,
+    #[serde(
+        rename = "h:List-Unsubscribe",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub list_unsubscribe: Option<String>,
+    #[serde(
+        rename = "h:List-Unsubscribe-Post",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub list_unsubscribe_post: Option<String>,
+    /// Accepted by Mailgun without delivering anything when "yes"
+    #[serde(rename = "o:testmode")]
+    pub test_mode: &'a str,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ErrorResponse {
+    pub message: String,
+}
@@ -0,0 +1,250 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use reqwest::StatusCode;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::domain::EmailAddress;
+use crate::email::{mailgun, retry_after, EmailData, EmailSender, SendError};
+use crate::settings::EmailClientSettings;
+
+/// Sends emails through Mailgun's messages API.
+pub struct MailgunClient {
+    http_client: reqwest::Client,
+    base_url: String,
+    api_key: Secret<String>,
+    domain: String,
+    from: EmailAddress,
+    sandbox: bool,
+}
+
+impl MailgunClient {
+    pub fn new(
+        base_url: String,
+        api_key: Secret<String>,
+        domain: String,
+        from: EmailAddress,
+        timeout: Duration,
+        sandbox: bool,
+    ) -> Self {
+        Self {
+            http_client: reqwest::Client::builder().timeout(timeout).build().unwrap(),
+            base_url,
+            api_key,
+            domain,
+            from,
+            sandbox,
+        }
+    }
+
+    pub fn from_settings(settings: &EmailClientSettings) -> Result<Self, anyhow::Error> {
+        let mailgun = settings
+            .mailgun
+            .as_ref()
+            .context("Mailgun settings are required by the mailgun provider")?;
+
+        Ok(Self::new(
+            settings.base_url.clone(),
+            settings.api_key.clone(),
+            mailgun.domain.clone(),
+            settings.sender.clone(),
+            Duration::from_millis(settings.timeout_millis),
+            settings.sandbox,
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for MailgunClient {
+    async fn send(&self, email: &EmailData) -> Result<(), SendError> {
+        let headers = email.unsubscribe_headers();
+        let header = |name| {
+            headers
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, value)| value.clone())
+        };
+        let body = mailgun::SendMessageBody {
+            from: self.from.as_ref(),
+            to: email.to.as_ref(),
+            subject: &email.subject,
+            html: Some(email.html_content.as_str().into()),
+            text: Some(email.plain_text()),
+            list_unsubscribe: header("List-Unsubscribe"),
+            list_unsubscribe_post: header("List-Unsubscribe-Post"),
+            test_mode: if self.sandbox { "yes" } else { "no" },
+        };
+
+        let response = self
+            .http_client
+            .post(format!(
+                "{}{}",
+                self.base_url,
+                mailgun::send_path(&self.domain)
+            ))
+            .form(&body)
+            .basic_auth(mailgun::API_USERNAME, Some(self.api_key.expose_secret()))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = retry_after(response.headers());
+            let details = response
+                .json::<mailgun::ErrorResponse>()
+                .await
+                .map(|e| e.message)
+                .unwrap_or_else(|_| "no error details".into());
+
+            if status == StatusCode::BAD_REQUEST
+                && details.starts_with(mailgun::INVALID_RECIPIENT_MESSAGE)
+            {
+                return Err(SendError::InvalidRecipient(format!(
+                    "Mailgun responded with {status}, {details}"
+                )));
+            }
+
+            return Err(SendError::from_status(
+                "Mailgun",
+                status,
+                retry_after,
+                &details,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use claims::{assert_err, assert_matches, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::{Fake, Faker};
+    use reqwest::{Method, StatusCode};
+    use secrecy::Secret;
+    use wiremock::matchers::{any, body_string_contains, header, header_regex, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::domain::EmailAddress;
+    use crate::email::test_helpers::{
+        assert_send_fails_transiently_on_server_errors, assert_send_times_out, fake_email_data,
+    };
+    use crate::email::{mailgun, EmailSender, MailgunClient, SendError};
+
+    fn email_client(base_url: String, sandbox: bool) -> MailgunClient {
+        MailgunClient::new(
+            base_url,
+            Secret::new(Faker.fake()),
+            "mg.example.com".into(),
+            EmailAddress::parse(SafeEmail().fake()).unwrap(),
+            Duration::from_millis(200),
+            sandbox,
+        )
+    }
+
+    #[tokio::test]
+    async fn send_email_fires_a_valid_request_to_server() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), false);
+
+        Mock::given(method(Method::POST))
+            .and(path(mailgun::send_path("mg.example.com")))
+            .and(header_regex("Authorization", r"Basic \S+"))
+            .and(header("Content-Type", "application/x-www-form-urlencoded"))
+            .and(body_string_contains("html="))
+            .and(body_string_contains("o%3Atestmode=no"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_ok!(result);
+    }
+
+    #[tokio::test]
+    async fn send_email_enables_test_mode_in_sandbox_mode() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), true);
+
+        Mock::given(body_string_contains("o%3Atestmode=yes"))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_ok!(result);
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_the_error_returned_by_the_server() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), false);
+
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(StatusCode::BAD_REQUEST)
+                    .set_body_json(serde_json::json!({"message": "'from' parameter is missing"})),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        let error = assert_err!(result);
+        assert_matches!(&error, SendError::Permanent(_));
+        assert!(error.to_string().contains("is missing"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_rejected_recipients_as_invalid() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), false);
+
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(StatusCode::BAD_REQUEST).set_body_json(serde_json::json!({
+                    "message": "'to' parameter is not a valid address. please check documentation"
+                })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        let error = assert_err!(result);
+        assert_matches!(&error, SendError::InvalidRecipient(_));
+        assert!(error.to_string().contains("not a valid address"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_transiently_if_the_server_fails() {
+        assert_send_fails_transiently_on_server_errors(|base_url| email_client(base_url, false))
+            .await;
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        assert_send_times_out(|base_url| email_client(base_url, false)).await;
+    }
+}
@@ -0,0 +1,93 @@
+use crate::email::{BatchEmailData, EmailData, EmailSender, SendError};
+use crate::metrics::METRICS;
+
+/// Counts the attempts of another sender, and its failures by kind.
+pub struct MeteredEmailSender {
+    inner: Box<dyn EmailSender>,
+    provider: &'static str,
+}
+
+impl MeteredEmailSender {
+    pub fn new(inner: Box<dyn EmailSender>, provider: &'static str) -> Self {
+        Self { inner, provider }
+    }
+
+    fn record(&self, result: &Result<(), SendError>) {
+        METRICS
+            .email_send_attempts
+            .with_label_values(&[self.provider])
+            .inc();
+
+        if let Err(e) = result {
+            METRICS
+                .email_send_failures
+                .with_label_values(&[self.provider, e.kind()])
+                .inc();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for MeteredEmailSender {
+    async fn send(&self, email: &EmailData) -> Result<(), SendError> {
+        let result = self.inner.send(email).await;
+        self.record(&result);
+        result
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.inner.max_batch_size()
+    }
+
+    async fn send_batch(&self, batch: &BatchEmailData) -> Result<(), SendError> {
+        let result = self.inner.send_batch(batch).await;
+        self.record(&result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::Fake;
+
+    use crate::domain::EmailAddress;
+    use crate::email::{EmailData, EmailSender, MeteredEmailSender, SendError};
+    use crate::metrics::METRICS;
+
+    struct FailingSender;
+
+    #[async_trait::async_trait]
+    impl EmailSender for FailingSender {
+        async fn send(&self, _: &EmailData) -> Result<(), SendError> {
+            Err(SendError::Transient(anyhow::anyhow!("Connection reset")))
+        }
+    }
+
+    #[tokio::test]
+    async fn attempts_and_failures_are_counted_by_provider() {
+        // Given
+        let email_sender = MeteredEmailSender::new(Box::new(FailingSender), "metered-test");
+        let email = EmailData {
+            to: EmailAddress::parse(SafeEmail().fake()).unwrap(),
+            subject: Sentence(1..2).fake(),
+            html_content: Paragraph(1..10).fake(),
+            text_content: None,
+            unsubscribe_url: None,
+        };
+
+        // When
+        email_sender.send(&email).await.unwrap_err();
+
+        // Then
+        let attempts = METRICS
+            .email_send_attempts
+            .with_label_values(&["metered-test"]);
+        let failures = METRICS
+            .email_send_failures
+            .with_label_values(&["metered-test", "transient"]);
+        assert_eq!(1, attempts.get());
+        assert_eq!(1, failures.get());
+    }
+}
@@ -0,0 +1,28 @@
+use lettre::message::header::{HeaderName, HeaderValue};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::Message;
+
+use crate::domain::EmailAddress;
+use crate::email::EmailData;
+
+/// Builds the RFC 5322 message of an email, with a Message-ID and plain-text and HTML alternatives.
+pub fn to_message(from: &EmailAddress, email: &EmailData) -> Result<Message, anyhow::Error> {
+    let mut message = Message::builder()
+        .from(from.as_ref().parse::<Mailbox>()?)
+        .to(email.to.as_ref().parse::<Mailbox>()?)
+        .subject(&email.subject)
+        .message_id(None)
+        .multipart(MultiPart::alternative_plain_html(
+            email.plain_text().into_owned(),
+            email.html_content.clone(),
+        ))?;
+
+    for (name, value) in email.unsubscribe_headers() {
+        message.headers_mut().insert_raw(HeaderValue::new(
+            HeaderName::new_from_ascii_str(name),
+            value,
+        ));
+    }
+
+    Ok(message)
+}
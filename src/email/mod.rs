@@ -1,6 +1,37 @@
-pub use client::*;
+pub use capture_client::*;
 pub use data::*;
+pub use error::*;
+pub use html_to_text::*;
+pub use maildir_client::*;
+pub use mailgun_client::*;
+pub use metered_sender::*;
+pub use postmark_client::*;
+pub use retry::*;
+pub use send_grid_client::*;
+pub use sender::*;
+pub use ses_client::*;
+pub use smtp_client::*;
+pub use templates::*;
 
-mod client;
+mod capture_client;
 mod data;
+mod error;
+mod html_to_text;
+mod maildir_client;
+pub mod mailgun;
+mod mailgun_client;
+mod metered_sender;
+mod mime;
+pub mod postmark;
+mod postmark_client;
+mod retry;
 pub mod send_grid;
+mod send_grid_client;
+mod sender;
+pub mod ses;
+mod ses_client;
+mod sigv4;
+mod smtp_client;
+mod templates;
+#[cfg(test)]
+mod test_helpers;
@@ -0,0 +1,37 @@
+use std::borrow::Cow;
+
+pub static SEND_PATH: &str = "/email";
+pub static SERVER_TOKEN_HEADER: &str = "X-Postmark-Server-Token";
+/// Accepted by Postmark without delivering anything
+pub static TEST_SERVER_TOKEN: &str = "POSTMARK_API_TEST";
+/// The recipient bounced, complained or unsubscribed before
+pub static INACTIVE_RECIPIENT_ERROR_CODE: i64 = 406;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SendEmailBody<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+    pub subject: &'a str,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html_body: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_body: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<Header>,
+    pub message_stream: &'a str,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ErrorResponse {
+    pub error_code: i64,
+    pub message: String,
+}
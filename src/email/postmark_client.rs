@@ -0,0 +1,219 @@
+use std::time::Duration;
+
+use secrecy::{ExposeSecret, Secret};
+
+use crate::domain::EmailAddress;
+use crate::email::{postmark, retry_after, EmailData, EmailSender, SendError};
+use crate::settings::EmailClientSettings;
+
+/// Sends emails through Postmark's email API.
+pub struct PostmarkClient {
+    http_client: reqwest::Client,
+    base_url: String,
+    server_token: Secret<String>,
+    from: EmailAddress,
+    sandbox: bool,
+}
+
+impl PostmarkClient {
+    pub fn new(
+        base_url: String,
+        server_token: Secret<String>,
+        from: EmailAddress,
+        timeout: Duration,
+        sandbox: bool,
+    ) -> Self {
+        Self {
+            http_client: reqwest::Client::builder().timeout(timeout).build().unwrap(),
+            base_url,
+            server_token,
+            from,
+            sandbox,
+        }
+    }
+
+    pub fn from_settings(settings: &EmailClientSettings) -> Self {
+        Self::new(
+            settings.base_url.clone(),
+            settings.api_key.clone(),
+            settings.sender.clone(),
+            Duration::from_millis(settings.timeout_millis),
+            settings.sandbox,
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for PostmarkClient {
+    async fn send(&self, email: &EmailData) -> Result<(), SendError> {
+        let body = postmark::SendEmailBody {
+            from: self.from.as_ref(),
+            to: email.to.as_ref(),
+            subject: &email.subject,
+            html_body: Some(email.html_content.as_str().into()),
+            text_body: Some(email.plain_text()),
+            headers: email
+                .unsubscribe_headers()
+                .into_iter()
+                .map(|(name, value)| postmark::Header {
+                    name: name.into(),
+                    value,
+                })
+                .collect(),
+            message_stream: "outbound",
+        };
+
+        let server_token = if self.sandbox {
+            postmark::TEST_SERVER_TOKEN
+        } else {
+            self.server_token.expose_secret()
+        };
+
+        let response = self
+            .http_client
+            .post(format!("{}{}", self.base_url, postmark::SEND_PATH))
+            .json(&body)
+            .header(postmark::SERVER_TOKEN_HEADER, server_token)
+            .header("Accept", "application/json")
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = retry_after(response.headers());
+            let error = response.json::<postmark::ErrorResponse>().await.ok();
+            let details = error
+                .as_ref()
+                .map(|e| format!("error code {}: {}", e.error_code, e.message))
+                .unwrap_or_else(|| "no error details".into());
+
+            if error.is_some_and(|e| e.error_code == postmark::INACTIVE_RECIPIENT_ERROR_CODE) {
+                return Err(SendError::InvalidRecipient(format!(
+                    "Postmark responded with {status}, {details}"
+                )));
+            }
+
+            return Err(SendError::from_status(
+                "Postmark",
+                status,
+                retry_after,
+                &details,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use claims::{assert_err, assert_matches, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::{Fake, Faker};
+    use reqwest::{Method, StatusCode};
+    use secrecy::Secret;
+    use wiremock::matchers::{any, header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::domain::EmailAddress;
+    use crate::email::test_helpers::{
+        assert_send_fails_transiently_on_server_errors, assert_send_times_out, fake_email_data,
+        BodyMatcher,
+    };
+    use crate::email::{postmark, EmailSender, PostmarkClient, SendError};
+
+    fn email_client(base_url: String, sandbox: bool) -> PostmarkClient {
+        PostmarkClient::new(
+            base_url,
+            Secret::new(Faker.fake()),
+            EmailAddress::parse(SafeEmail().fake()).unwrap(),
+            Duration::from_millis(200),
+            sandbox,
+        )
+    }
+
+    #[tokio::test]
+    async fn send_email_fires_a_valid_request_to_server() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), false);
+
+        Mock::given(method(Method::POST))
+            .and(path(postmark::SEND_PATH))
+            .and(header_exists(postmark::SERVER_TOKEN_HEADER))
+            .and(header("Content-Type", "application/json"))
+            .and(BodyMatcher(|body| {
+                serde_json::from_slice::<postmark::SendEmailBody>(body).is_ok()
+            }))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_ok!(result);
+    }
+
+    #[tokio::test]
+    async fn send_email_uses_the_test_server_token_in_sandbox_mode() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), true);
+
+        Mock::given(header(
+            postmark::SERVER_TOKEN_HEADER,
+            postmark::TEST_SERVER_TOKEN,
+        ))
+        .respond_with(ResponseTemplate::new(StatusCode::OK))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_ok!(result);
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_the_error_returned_by_the_server() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), false);
+
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(StatusCode::UNPROCESSABLE_ENTITY).set_body_json(
+                    serde_json::json!({"ErrorCode": 406, "Message": "Inactive recipient"}),
+                ),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        let error = assert_err!(result);
+        assert_matches!(&error, SendError::InvalidRecipient(_));
+        assert!(error.to_string().contains("Inactive recipient"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_transiently_if_the_server_fails() {
+        assert_send_fails_transiently_on_server_errors(|base_url| email_client(base_url, false))
+            .await;
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        assert_send_times_out(|base_url| email_client(base_url, false)).await;
+    }
+}
@@ -0,0 +1,579 @@
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use tracing::Span;
+
+use crate::email::{BatchEmailData, EmailData, EmailSender, SendError};
+use crate::settings::{CircuitBreakerSettings, EmailRetrySettings};
+
+/// Retries the transient failures of another sender with jittered exponential backoff, and fails
+/// fast while the provider keeps failing.
+///
+/// Failures that remain are left to the email delivery queue, which retries over a longer time.
+pub struct RetryingEmailSender {
+    inner: Box<dyn EmailSender>,
+    settings: EmailRetrySettings,
+    circuit_breaker: CircuitBreaker,
+}
+
+impl RetryingEmailSender {
+    pub fn new(
+        inner: Box<dyn EmailSender>,
+        settings: EmailRetrySettings,
+        circuit_breaker_settings: &CircuitBreakerSettings,
+    ) -> Self {
+        Self {
+            inner,
+            settings,
+            circuit_breaker: CircuitBreaker::new(circuit_breaker_settings),
+        }
+    }
+
+    /// The delay before the next attempt, up to the doubling backoff (full jitter).
+    fn backoff_delay(&self, n_failed_attempts: u32) -> Duration {
+        let exponent = n_failed_attempts.saturating_sub(1).min(31);
+        let max_delay_millis = self
+            .settings
+            .base_backoff_millis
+            .saturating_mul(2u64.pow(exponent))
+            .min(self.settings.max_backoff_millis);
+
+        Duration::from_millis(rand::thread_rng().gen_range(0..=max_delay_millis))
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for RetryingEmailSender {
+    #[tracing::instrument(
+        name = "Sending email with retries",
+        skip_all,
+        fields(n_attempts, outcome)
+    )]
+    async fn send(&self, email: &EmailData) -> Result<(), SendError> {
+        self.with_retries(|| self.inner.send(email)).await
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.inner.max_batch_size()
+    }
+
+    /// Retries every request of the batch on its own, as providers accept or reject requests as
+    /// a whole, so that a failure never sends the requests before it again.
+    #[tracing::instrument(
+        name = "Sending email batch with retries",
+        skip_all,
+        fields(n_recipients = batch.recipients.len())
+    )]
+    async fn send_batch(&self, batch: &BatchEmailData) -> Result<(), SendError> {
+        for chunk in batch.chunks(self.inner.max_batch_size()) {
+            self.send_chunk(&chunk).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl RetryingEmailSender {
+    #[tracing::instrument(
+        name = "Sending email batch request with retries",
+        skip_all,
+        fields(n_recipients = chunk.recipients.len(), n_attempts, outcome)
+    )]
+    async fn send_chunk(&self, chunk: &BatchEmailData) -> Result<(), SendError> {
+        self.with_retries(|| self.inner.send_batch(chunk)).await
+    }
+
+    /// Makes attempts until one succeeds or fails for good, recording them on the current span.
+    async fn with_retries<F, Fut>(&self, attempt: F) -> Result<(), SendError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<(), SendError>>,
+    {
+        let mut n_attempts = 0;
+
+        let (outcome, result) = loop {
+            let permit = match self.circuit_breaker.try_acquire() {
+                Ok(permit) => permit,
+                Err(e) => break ("circuit_open", Err(e)),
+            };
+
+            n_attempts += 1;
+            let e = match attempt().await {
+                Ok(()) => {
+                    permit.record_success();
+                    break ("sent", Ok(()));
+                }
+                Err(e) => e,
+            };
+
+            if !e.is_retryable() {
+                // The provider works, it is the email or the credentials that were rejected
+                permit.record_success();
+                break ("rejected", Err(e));
+            }
+            permit.record_failure();
+
+            if n_attempts >= self.settings.max_attempts {
+                break ("failed", Err(e));
+            }
+
+            let delay = match e.retry_after() {
+                Some(retry_after)
+                    if retry_after > Duration::from_millis(self.settings.max_backoff_millis) =>
+                {
+                    break ("failed", Err(e));
+                }
+                Some(retry_after) => retry_after,
+                None => self.backoff_delay(n_attempts),
+            };
+
+            tracing::warn!(
+                "Failed to send an email on attempt {}, retrying in {:?}: {}",
+                n_attempts,
+                delay,
+                e
+            );
+            tokio::time::sleep(delay).await;
+        };
+
+        Span::current()
+            .record("n_attempts", n_attempts)
+            .record("outcome", outcome);
+
+        result
+    }
+}
+
+enum CircuitState {
+    Closed {
+        n_consecutive_failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// A single trial send is in flight after the circuit was open
+    HalfOpen,
+}
+
+struct CircuitBreaker {
+    state: Mutex<CircuitState>,
+    failure_threshold: u32,
+    open_duration: Duration,
+}
+
+impl CircuitBreaker {
+    fn new(settings: &CircuitBreakerSettings) -> Self {
+        Self {
+            state: Mutex::new(CircuitState::Closed {
+                n_consecutive_failures: 0,
+            }),
+            failure_threshold: settings.failure_threshold,
+            open_duration: Duration::from_secs(settings.open_duration_secs),
+        }
+    }
+
+    fn try_acquire(&self) -> Result<Permit<'_>, SendError> {
+        let mut state = self.state.lock().unwrap();
+
+        match *state {
+            CircuitState::Closed { .. } => Ok(Permit {
+                circuit_breaker: self,
+                is_trial: false,
+            }),
+            CircuitState::Open { until } => {
+                let now = Instant::now();
+                if now < until {
+                    return Err(SendError::CircuitOpen {
+                        retry_after: until - now,
+                    });
+                }
+
+                tracing::info!("Trying the email provider again after the circuit was open");
+                *state = CircuitState::HalfOpen;
+                Ok(Permit {
+                    circuit_breaker: self,
+                    is_trial: true,
+                })
+            }
+            CircuitState::HalfOpen => Err(SendError::CircuitOpen {
+                retry_after: Duration::ZERO,
+            }),
+        }
+    }
+
+    fn record_success(&self) {
+        *self.state.lock().unwrap() = CircuitState::Closed {
+            n_consecutive_failures: 0,
+        };
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        let n_consecutive_failures = match *state {
+            CircuitState::Closed {
+                n_consecutive_failures,
+            } => n_consecutive_failures + 1,
+            CircuitState::Open { .. } | CircuitState::HalfOpen => self.failure_threshold,
+        };
+
+        *state = if n_consecutive_failures >= self.failure_threshold {
+            tracing::error!(
+                "Opening the circuit after {} consecutive failures of the email provider",
+                n_consecutive_failures
+            );
+            CircuitState::Open {
+                until: Instant::now() + self.open_duration,
+            }
+        } else {
+            CircuitState::Closed {
+                n_consecutive_failures,
+            }
+        };
+    }
+}
+
+/// Allows an attempt, whose outcome is recorded with the permit.
+///
+/// The trial attempt of a half-open circuit may not complete, e.g. when the send is cancelled, so
+/// dropping its permit opens the circuit again, due for another trial.
+#[must_use]
+struct Permit<'a> {
+    circuit_breaker: &'a CircuitBreaker,
+    is_trial: bool,
+}
+
+impl Permit<'_> {
+    fn record_success(mut self) {
+        self.is_trial = false;
+        self.circuit_breaker.record_success();
+    }
+
+    fn record_failure(mut self) {
+        self.is_trial = false;
+        self.circuit_breaker.record_failure();
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if !self.is_trial {
+            return;
+        }
+
+        let mut state = self.circuit_breaker.state.lock().unwrap();
+        if let CircuitState::HalfOpen = *state {
+            *state = CircuitState::Open {
+                until: Instant::now(),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use claims::{assert_err, assert_matches, assert_ok};
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::Fake;
+    use reqwest::StatusCode;
+
+    use crate::domain::EmailAddress;
+    use crate::email::test_helpers::fake_email_data;
+    use crate::email::{
+        BatchEmailData, BatchRecipient, EmailData, EmailSender, RetryingEmailSender, SendError,
+    };
+    use crate::settings::{CircuitBreakerSettings, EmailRetrySettings};
+
+    use super::CircuitBreaker;
+
+    /// A provider's error status, and its `Retry-After`.
+    type Failure = (StatusCode, Option<Duration>);
+
+    /// Replies with the given failures or successes in order, and with success once they run out.
+    #[derive(Clone)]
+    struct ScriptedSender {
+        statuses: Arc<Mutex<VecDeque<Option<Failure>>>>,
+        n_calls: Arc<Mutex<u32>>,
+        max_batch_size: usize,
+        /// The recipients of the batches that were sent
+        sent_to: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ScriptedSender {
+        fn failing_with(statuses: &[Failure]) -> Self {
+            Self::replying_with(&statuses.iter().copied().map(Some).collect::<Vec<_>>())
+        }
+
+        fn replying_with(statuses: &[Option<Failure>]) -> Self {
+            Self {
+                statuses: Arc::new(Mutex::new(statuses.iter().copied().collect())),
+                n_calls: Default::default(),
+                max_batch_size: 10,
+                sent_to: Default::default(),
+            }
+        }
+
+        fn n_calls(&self) -> u32 {
+            *self.n_calls.lock().unwrap()
+        }
+
+        fn reply(&self) -> Result<(), SendError> {
+            *self.n_calls.lock().unwrap() += 1;
+
+            match self.statuses.lock().unwrap().pop_front().flatten() {
+                Some((status, retry_after)) => Err(SendError::from_status(
+                    "Scripted",
+                    status,
+                    retry_after,
+                    "no error details",
+                )),
+                None => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl EmailSender for ScriptedSender {
+        async fn send(&self, _: &EmailData) -> Result<(), SendError> {
+            self.reply()
+        }
+
+        fn max_batch_size(&self) -> usize {
+            self.max_batch_size
+        }
+
+        async fn send_batch(&self, batch: &BatchEmailData) -> Result<(), SendError> {
+            self.reply()?;
+            self.sent_to.lock().unwrap().extend(
+                batch
+                    .recipients
+                    .iter()
+                    .map(|recipient| recipient.to.as_ref().to_string()),
+            );
+            Ok(())
+        }
+    }
+
+    fn email_sender(inner: &ScriptedSender, failure_threshold: u32) -> RetryingEmailSender {
+        RetryingEmailSender::new(
+            Box::new(inner.clone()),
+            EmailRetrySettings {
+                max_attempts: 3,
+                base_backoff_millis: 10,
+                max_backoff_millis: 100,
+            },
+            &CircuitBreakerSettings {
+                failure_threshold,
+                open_duration_secs: 60,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried() {
+        // Given
+        let inner = ScriptedSender::failing_with(&[
+            (StatusCode::SERVICE_UNAVAILABLE, None),
+            (StatusCode::TOO_MANY_REQUESTS, None),
+        ]);
+        let email_sender = email_sender(&inner, 10);
+
+        // When
+        let result = email_sender.send(&fake_email_data()).await;
+
+        // Then
+        assert_ok!(result);
+        assert_eq!(3, inner.n_calls());
+    }
+
+    fn fake_batch_email_data(n_recipients: usize) -> BatchEmailData {
+        BatchEmailData {
+            subject: Sentence(1..2).fake(),
+            html_content: Paragraph(1..10).fake(),
+            text_content: None,
+            recipients: (0..n_recipients)
+                .map(|i| BatchRecipient {
+                    to: EmailAddress::parse(format!("subscriber{i}@example.com")).unwrap(),
+                    substitutions: Default::default(),
+                    unsubscribe_url: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn batches_are_retried_as_a_whole() {
+        // Given
+        let inner = ScriptedSender::failing_with(&[(StatusCode::SERVICE_UNAVAILABLE, None)]);
+        let email_sender = email_sender(&inner, 10);
+        let batch = fake_batch_email_data(3);
+
+        // When
+        let result = email_sender.send_batch(&batch).await;
+
+        // Then
+        assert_ok!(result);
+        assert_eq!(2, inner.n_calls());
+    }
+
+    #[tokio::test]
+    async fn only_the_failed_request_of_a_batch_is_retried() {
+        // Given
+        let inner = ScriptedSender {
+            max_batch_size: 2,
+            ..ScriptedSender::replying_with(&[None, Some((StatusCode::SERVICE_UNAVAILABLE, None))])
+        };
+        let email_sender = email_sender(&inner, 10);
+        let batch = fake_batch_email_data(5);
+
+        // When
+        let result = email_sender.send_batch(&batch).await;
+
+        // Then
+        assert_ok!(result);
+        assert_eq!(4, inner.n_calls());
+        let sent_to = inner.sent_to.lock().unwrap().clone();
+        let expected = batch
+            .recipients
+            .iter()
+            .map(|recipient| recipient.to.as_ref().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(expected, sent_to);
+    }
+
+    #[tokio::test]
+    async fn rejected_emails_are_not_retried() {
+        // Given
+        let inner = ScriptedSender::failing_with(&[(StatusCode::BAD_REQUEST, None)]);
+        let email_sender = email_sender(&inner, 10);
+
+        // When
+        let result = email_sender.send(&fake_email_data()).await;
+
+        // Then
+        assert_err!(result);
+        assert_eq!(1, inner.n_calls());
+    }
+
+    #[tokio::test]
+    async fn retries_stop_after_the_max_attempts() {
+        // Given
+        let inner = ScriptedSender::failing_with(&[(StatusCode::BAD_GATEWAY, None); 5]);
+        let email_sender = email_sender(&inner, 10);
+
+        // When
+        let result = email_sender.send(&fake_email_data()).await;
+
+        // Then
+        assert_err!(result);
+        assert_eq!(3, inner.n_calls());
+    }
+
+    #[tokio::test]
+    async fn retry_after_is_honoured() {
+        // Given
+        let retry_after = Duration::from_millis(80);
+        let inner =
+            ScriptedSender::failing_with(&[(StatusCode::TOO_MANY_REQUESTS, Some(retry_after))]);
+        let email_sender = email_sender(&inner, 10);
+        let start = Instant::now();
+
+        // When
+        let result = email_sender.send(&fake_email_data()).await;
+
+        // Then
+        assert_ok!(result);
+        assert!(start.elapsed() >= retry_after);
+    }
+
+    #[tokio::test]
+    async fn retry_after_longer_than_the_max_backoff_is_left_to_the_queue() {
+        // Given
+        let inner = ScriptedSender::failing_with(&[(
+            StatusCode::TOO_MANY_REQUESTS,
+            Some(Duration::from_secs(3600)),
+        )]);
+        let email_sender = email_sender(&inner, 10);
+
+        // When
+        let result = email_sender.send(&fake_email_data()).await;
+
+        // Then
+        assert_err!(result);
+        assert_eq!(1, inner.n_calls());
+    }
+
+    #[tokio::test]
+    async fn the_circuit_opens_when_the_provider_keeps_failing() {
+        // Given
+        let inner = ScriptedSender::failing_with(&[(StatusCode::INTERNAL_SERVER_ERROR, None); 3]);
+        let email_sender = email_sender(&inner, 3);
+        email_sender.send(&fake_email_data()).await.unwrap_err();
+
+        // When
+        let result = email_sender.send(&fake_email_data()).await;
+
+        // Then
+        assert_matches!(result, Err(SendError::CircuitOpen { .. }));
+        assert_eq!(3, inner.n_calls());
+    }
+
+    #[tokio::test]
+    async fn rejected_emails_do_not_open_the_circuit() {
+        // Given
+        let inner = ScriptedSender::failing_with(&[(StatusCode::BAD_REQUEST, None); 3]);
+        let email_sender = email_sender(&inner, 2);
+
+        // When
+        for _ in 0..3 {
+            email_sender.send(&fake_email_data()).await.unwrap_err();
+        }
+        let result = email_sender.send(&fake_email_data()).await;
+
+        // Then
+        assert_ok!(result);
+        assert_eq!(4, inner.n_calls());
+    }
+
+    #[test]
+    fn a_dropped_trial_leaves_the_circuit_due_for_another_one() {
+        // Given
+        let circuit_breaker = CircuitBreaker::new(&CircuitBreakerSettings {
+            failure_threshold: 1,
+            open_duration_secs: 0,
+        });
+        circuit_breaker.try_acquire().unwrap().record_failure();
+        let trial = circuit_breaker.try_acquire().unwrap();
+
+        // When
+        drop(trial);
+
+        // Then
+        assert!(circuit_breaker.try_acquire().is_ok());
+    }
+
+    #[test]
+    fn a_trial_in_flight_keeps_the_circuit_from_other_attempts() {
+        // Given
+        let circuit_breaker = CircuitBreaker::new(&CircuitBreakerSettings {
+            failure_threshold: 1,
+            open_duration_secs: 0,
+        });
+        circuit_breaker.try_acquire().unwrap().record_failure();
+
+        // When
+        let _trial = circuit_breaker.try_acquire().unwrap();
+
+        // Then
+        assert_matches!(
+            circuit_breaker.try_acquire().err(),
+            Some(SendError::CircuitOpen { .. })
+        );
+    }
+}
@@ -1,20 +1,31 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 
 pub static SEND_PATH: &str = "/v3/mail/send";
 
+/// The most personalizations, and so recipients, that a single request may have.
+pub const MAX_PERSONALIZATIONS: usize = 1000;
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct MailSendBody<'a> {
     pub personalizations: Vec<Personalization<'a>>,
     pub from: From<'a>,
     pub subject: &'a str,
     pub content: Vec<Content<'a>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<HashMap<String, String>>,
     pub mail_settings: MailSettings,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Personalization<'a> {
+    /// Everyone in a personalization sees the others, so batches have one recipient in each
     #[serde(borrow)]
     pub to: Vec<To<'a>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub substitutions: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<HashMap<String, String>>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -43,3 +54,16 @@ pub struct MailSettings {
 pub struct SandboxMode {
     pub enable: bool,
 }
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ErrorResponse {
+    pub errors: Vec<ResponseError>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ResponseError {
+    pub message: String,
+    /// The request field at fault, if any
+    #[serde(default)]
+    pub field: Option<String>,
+}
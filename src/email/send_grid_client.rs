@@ -0,0 +1,604 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::domain::EmailAddress;
+use crate::email::{
+    replace_keys, retry_after, send_grid, BatchEmailData, EmailData, EmailSender, SendError,
+};
+use crate::html::escape_html;
+use crate::settings::EmailClientSettings;
+
+/// Sends emails through SendGrid's v3 mail send API.
+pub struct SendGridClient {
+    http_client: reqwest::Client,
+    base_url: String,
+    api_key: Secret<String>,
+    from: EmailAddress,
+    sandbox: bool,
+}
+
+impl SendGridClient {
+    pub fn new(
+        base_url: String,
+        api_key: Secret<String>,
+        from: EmailAddress,
+        timeout: Duration,
+        sandbox: bool,
+    ) -> Self {
+        Self {
+            http_client: reqwest::Client::builder().timeout(timeout).build().unwrap(),
+            base_url,
+            from,
+            api_key,
+            sandbox,
+        }
+    }
+
+    pub fn from_settings(settings: &EmailClientSettings) -> Self {
+        Self::new(
+            settings.base_url.clone(),
+            settings.api_key.clone(),
+            settings.sender.clone(),
+            Duration::from_millis(settings.timeout_millis),
+            settings.sandbox,
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for SendGridClient {
+    async fn send(&self, email: &EmailData) -> Result<(), SendError> {
+        let personalizations = vec![send_grid::Personalization {
+            to: vec![send_grid::To {
+                email: email.to.as_ref(),
+            }],
+            substitutions: None,
+            headers: None,
+        }];
+        let mut body = self.mail_send_body(
+            personalizations,
+            &email.subject,
+            email.plain_text(),
+            &email.html_content,
+        );
+        body.headers = email
+            .unsubscribe_url
+            .as_ref()
+            .map(|_| headers(email.unsubscribe_headers()));
+
+        self.post(&body).await
+    }
+
+    fn max_batch_size(&self) -> usize {
+        send_grid::MAX_PERSONALIZATIONS
+    }
+
+    /// Sends up to [`send_grid::MAX_PERSONALIZATIONS`] recipients per request, each in their own
+    /// personalization so that they do not see each other.
+    ///
+    /// SendGrid substitutes the same value everywhere, so the keys are first replaced by a token
+    /// for the plain parts and one for the HTML content, the latter given the escaped value.
+    async fn send_batch(&self, batch: &BatchEmailData) -> Result<(), SendError> {
+        let keys = batch.substitution_keys();
+        let subject = replace_keys(&batch.subject, &keys, |i, _| substitution_token("text", i));
+        let text_content = replace_keys(&batch.plain_text(), &keys, |i, _| {
+            substitution_token("text", i)
+        });
+        let html_content = replace_keys(&batch.html_content, &keys, |i, _| {
+            substitution_token("html", i)
+        });
+
+        for recipients in batch.recipients.chunks(send_grid::MAX_PERSONALIZATIONS) {
+            let personalizations = recipients
+                .iter()
+                .map(|recipient| send_grid::Personalization {
+                    to: vec![send_grid::To {
+                        email: recipient.to.as_ref(),
+                    }],
+                    substitutions: (!keys.is_empty()).then(|| {
+                        keys.iter()
+                            .enumerate()
+                            .flat_map(|(i, key)| {
+                                let value = recipient.substitutions.get(*key);
+                                [
+                                    (
+                                        substitution_token("text", i),
+                                        value.map_or(*key, String::as_str).to_string(),
+                                    ),
+                                    (
+                                        substitution_token("html", i),
+                                        value.map_or_else(|| key.to_string(), |v| escape_html(v)),
+                                    ),
+                                ]
+                            })
+                            .collect()
+                    }),
+                    headers: recipient
+                        .unsubscribe_url
+                        .as_ref()
+                        .map(|_| headers(recipient.unsubscribe_headers())),
+                })
+                .collect();
+            let body = self.mail_send_body(
+                personalizations,
+                &subject,
+                Cow::from(&text_content),
+                &html_content,
+            );
+
+            self.post(&body).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A token that no key is a part of, so that SendGrid substitutes them in any order.
+fn substitution_token(part: &str, i: usize) -> String {
+    format!("%%zero2prod-{part}-{i}%%")
+}
+
+impl SendGridClient {
+    fn mail_send_body<'a>(
+        &'a self,
+        personalizations: Vec<send_grid::Personalization<'a>>,
+        subject: &'a str,
+        text_content: Cow<'a, str>,
+        html_content: &'a str,
+    ) -> send_grid::MailSendBody<'a> {
+        send_grid::MailSendBody {
+            personalizations,
+            from: send_grid::From {
+                email: self.from.as_ref(),
+            },
+            subject,
+            // SendGrid requires the plain-text content first
+            content: vec![
+                send_grid::Content {
+                    mime_type: "text/plain",
+                    value: text_content,
+                },
+                send_grid::Content {
+                    mime_type: "text/html",
+                    value: html_content.into(),
+                },
+            ],
+            headers: None,
+            mail_settings: send_grid::MailSettings {
+                sandbox_mode: send_grid::SandboxMode {
+                    enable: self.sandbox,
+                },
+            },
+        }
+    }
+
+    async fn post(&self, body: &send_grid::MailSendBody<'_>) -> Result<(), SendError> {
+        let response = self
+            .http_client
+            .post(format!("{}{}", self.base_url, send_grid::SEND_PATH))
+            .json(body)
+            .header(
+                "Authorization",
+                format!("Bearer {token}", token = self.api_key.expose_secret()),
+            )
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = retry_after(response.headers());
+            let errors = response
+                .json::<send_grid::ErrorResponse>()
+                .await
+                .map(|e| e.errors)
+                .unwrap_or_default();
+            let details = if errors.is_empty() {
+                "no error details".into()
+            } else {
+                errors
+                    .iter()
+                    .map(|e| e.message.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+
+            // The errors name the invalid fields, e.g. `personalizations.0.to.0.email`
+            let is_recipient_invalid = errors.iter().any(|e| {
+                e.field.as_deref().is_some_and(|field| {
+                    field.starts_with("personalizations") && field.contains(".to")
+                })
+            });
+            if status == StatusCode::BAD_REQUEST && is_recipient_invalid {
+                return Err(SendError::InvalidRecipient(format!(
+                    "SendGrid responded with {status}, {details}"
+                )));
+            }
+
+            return Err(SendError::from_status(
+                "SendGrid",
+                status,
+                retry_after,
+                &details,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn headers(headers: Vec<(&'static str, String)>) -> HashMap<String, String> {
+    headers
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use claims::{assert_matches, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::Paragraph;
+    use fake::{Fake, Faker};
+    use reqwest::{Method, StatusCode};
+    use secrecy::Secret;
+    use wiremock::matchers::{any, header, header_regex, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::domain::EmailAddress;
+    use crate::email::test_helpers::{
+        assert_send_fails_transiently_on_server_errors, assert_send_times_out, fake_email_data,
+        BodyMatcher,
+    };
+    use crate::email::{
+        send_grid, BatchEmailData, BatchRecipient, EmailData, EmailSender, SendError,
+        SendGridClient,
+    };
+
+    fn email_client(base_url: String) -> SendGridClient {
+        SendGridClient::new(
+            base_url,
+            Secret::new(Faker.fake()),
+            EmailAddress::parse(SafeEmail().fake()).unwrap(),
+            Duration::from_millis(200),
+            true,
+        )
+    }
+
+    #[tokio::test]
+    async fn send_email_fires_a_valid_request_to_server() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(method(Method::POST))
+            .and(path(send_grid::SEND_PATH))
+            .and(header_regex("Authorization", r"Bearer \w+"))
+            .and(header("Content-Type", "application/json"))
+            .and(BodyMatcher(|body| {
+                serde_json::from_slice::<send_grid::MailSendBody>(body).is_ok()
+            }))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_ok!(result);
+    }
+
+    #[tokio::test]
+    async fn send_email_adds_the_unsubscribe_headers() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let unsubscribe_url = reqwest::Url::parse("https://example.com/unsubscribe").unwrap();
+        let email = EmailData {
+            unsubscribe_url: Some(unsubscribe_url.clone()),
+            ..fake_email_data()
+        };
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        email_client.send(&email).await.unwrap();
+
+        // Then
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: send_grid::MailSendBody = serde_json::from_slice(&request.body).unwrap();
+        let headers = body.headers.unwrap();
+        assert_eq!(format!("<{unsubscribe_url}>"), headers["List-Unsubscribe"]);
+        assert_eq!(
+            "List-Unsubscribe=One-Click",
+            headers["List-Unsubscribe-Post"]
+        );
+    }
+
+    #[tokio::test]
+    async fn send_email_sends_the_plain_text_content_before_the_html_content() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let email = EmailData {
+            html_content: r#"<p>Hello, <a href="https://example.com">world</a>!</p>"#.into(),
+            ..fake_email_data()
+        };
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        email_client.send(&email).await.unwrap();
+
+        // Then
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: send_grid::MailSendBody = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(2, body.content.len());
+        assert_eq!("text/plain", body.content[0].mime_type);
+        assert_eq!("Hello, world (https://example.com)!", body.content[0].value);
+        assert_eq!("text/html", body.content[1].mime_type);
+        assert_eq!(email.html_content, body.content[1].value);
+    }
+
+    #[tokio::test]
+    async fn send_email_prefers_the_given_plain_text_content() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let email = EmailData {
+            text_content: Some(Paragraph(1..10).fake()),
+            ..fake_email_data()
+        };
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        email_client.send(&email).await.unwrap();
+
+        // Then
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: send_grid::MailSendBody = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(email.text_content.unwrap(), body.content[0].value);
+    }
+
+    fn fake_batch_email_data(n_recipients: usize) -> BatchEmailData {
+        BatchEmailData {
+            subject: "Hello -name-".into(),
+            html_content: Paragraph(1..10).fake(),
+            text_content: None,
+            recipients: (0..n_recipients)
+                .map(|i| BatchRecipient {
+                    to: EmailAddress::parse(format!("subscriber{i}@example.com")).unwrap(),
+                    substitutions: HashMap::from([("-name-".into(), format!("Subscriber {i}"))]),
+                    unsubscribe_url: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn send_batch_sends_up_to_the_max_personalizations_per_request() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let batch = fake_batch_email_data(send_grid::MAX_PERSONALIZATIONS + 1);
+
+        Mock::given(method(Method::POST))
+            .and(path(send_grid::SEND_PATH))
+            .and(BodyMatcher(|body| {
+                serde_json::from_slice::<send_grid::MailSendBody>(body).is_ok()
+            }))
+            .respond_with(ResponseTemplate::new(StatusCode::ACCEPTED))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send_batch(&batch).await;
+
+        // Then
+        assert_ok!(result);
+        let requests = mock_server.received_requests().await.unwrap();
+        let n_personalizations = requests
+            .iter()
+            .map(|r| {
+                serde_json::from_slice::<send_grid::MailSendBody>(&r.body)
+                    .unwrap()
+                    .personalizations
+                    .len()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![send_grid::MAX_PERSONALIZATIONS, 1], n_personalizations);
+    }
+
+    #[tokio::test]
+    async fn send_batch_keeps_recipients_and_their_substitutions_apart() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let mut batch = fake_batch_email_data(2);
+        let unsubscribe_url = reqwest::Url::parse("https://example.com/unsubscribe").unwrap();
+        batch.recipients[1].unsubscribe_url = Some(unsubscribe_url.clone());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(StatusCode::ACCEPTED))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        email_client.send_batch(&batch).await.unwrap();
+
+        // Then
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: send_grid::MailSendBody = serde_json::from_slice(&request.body).unwrap();
+        for (i, personalization) in body.personalizations.iter().enumerate() {
+            assert_eq!(1, personalization.to.len());
+            assert_eq!(batch.recipients[i].to.as_ref(), personalization.to[0].email);
+            assert_eq!(
+                format!("Hello Subscriber {i}"),
+                substitute(body.subject, personalization)
+            );
+        }
+        assert_eq!(None, body.personalizations[0].headers);
+        assert_eq!(
+            format!("<{unsubscribe_url}>"),
+            body.personalizations[1].headers.as_ref().unwrap()["List-Unsubscribe"]
+        );
+    }
+
+    #[tokio::test]
+    async fn send_batch_escapes_the_substitutions_in_the_html_content_only() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let mut batch = fake_batch_email_data(1);
+        batch.html_content = "<p>Hello -name-</p>".into();
+        batch.recipients[0].substitutions =
+            HashMap::from([("-name-".into(), "<b>Tom & Jerry</b>".into())]);
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(StatusCode::ACCEPTED))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        email_client.send_batch(&batch).await.unwrap();
+
+        // Then
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: send_grid::MailSendBody = serde_json::from_slice(&request.body).unwrap();
+        let personalization = &body.personalizations[0];
+        assert_eq!(
+            "Hello <b>Tom & Jerry</b>",
+            substitute(body.subject, personalization)
+        );
+        assert_eq!(
+            "Hello <b>Tom & Jerry</b>",
+            substitute(&body.content[0].value, personalization)
+        );
+        assert_eq!(
+            "<p>Hello &lt;b&gt;Tom &amp; Jerry&lt;/b&gt;</p>",
+            substitute(&body.content[1].value, personalization)
+        );
+    }
+
+    fn substitute(content: &str, personalization: &send_grid::Personalization) -> String {
+        personalization
+            .substitutions
+            .iter()
+            .flatten()
+            .fold(content.to_string(), |content, (key, value)| {
+                content.replace(key, value)
+            })
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_rate_limiting_with_the_retry_after() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(StatusCode::TOO_MANY_REQUESTS)
+                    .insert_header("Retry-After", "30"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        let error = result.unwrap_err();
+        assert_matches!(error, SendError::RateLimited { .. });
+        assert_eq!(Some(Duration::from_secs(30)), error.retry_after());
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_invalid_recipients() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(StatusCode::BAD_REQUEST).set_body_json(serde_json::json!({
+                    "errors": [{
+                        "message": "Does not contain a valid address.",
+                        "field": "personalizations.0.to.0.email",
+                        "help": null,
+                    }]
+                })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_matches!(result, Err(SendError::InvalidRecipient(message)) if message.contains("Does not contain a valid address."));
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_rejected_credentials() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(StatusCode::UNAUTHORIZED).set_body_json(
+                serde_json::json!({
+                    "errors": [{
+                        "message": "The provided authorization grant is invalid, expired, or revoked",
+                        "field": null,
+                        "help": null,
+                    }]
+                }),
+            ))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_matches!(result, Err(SendError::AuthFailure(_)));
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_transiently_if_the_server_fails() {
+        assert_send_fails_transiently_on_server_errors(email_client).await;
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        assert_send_times_out(email_client).await;
+    }
+}
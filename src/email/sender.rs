@@ -0,0 +1,65 @@
+use sqlx::PgPool;
+
+use crate::email::{
+    BatchEmailData, CaptureClient, EmailData, MaildirClient, MailgunClient, MeteredEmailSender,
+    PostmarkClient, RetryingEmailSender, SendError, SendGridClient, SesClient, SmtpClient,
+};
+use crate::settings::{EmailClientSettings, EmailProvider};
+
+/// A transport that emails can be sent through.
+#[async_trait::async_trait]
+pub trait EmailSender: Send + Sync {
+    async fn send(&self, email: &EmailData) -> Result<(), SendError>;
+
+    /// The most recipients that [`EmailSender::send_batch`] sends in a single request.
+    fn max_batch_size(&self) -> usize {
+        1
+    }
+
+    /// Sends the email to every recipient, one at a time unless the provider supports batches.
+    ///
+    /// Stops at the first failure, after the emails to the recipients before it were sent.
+    async fn send_batch(&self, batch: &BatchEmailData) -> Result<(), SendError> {
+        for email in batch.personalized() {
+            self.send(&email).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds the sender of the configured provider, retrying the ones sending over the network.
+///
+/// Every attempt is counted in the metrics, including the retried ones.
+pub fn build_email_sender(settings: &EmailClientSettings, pool: &PgPool) -> Box<dyn EmailSender> {
+    let email_sender: Box<dyn EmailSender> = match settings.provider {
+        EmailProvider::SendGrid => Box::new(SendGridClient::from_settings(settings)),
+        EmailProvider::Postmark => Box::new(PostmarkClient::from_settings(settings)),
+        EmailProvider::Mailgun => Box::new(
+            MailgunClient::from_settings(settings).expect("Failed to build the Mailgun client"),
+        ),
+        EmailProvider::Ses => {
+            Box::new(SesClient::from_settings(settings).expect("Failed to build the SES client"))
+        }
+        EmailProvider::Smtp => {
+            Box::new(SmtpClient::from_settings(settings).expect("Failed to build the SMTP client"))
+        }
+        EmailProvider::Maildir => Box::new(
+            MaildirClient::from_settings(settings).expect("Failed to build the Maildir client"),
+        ),
+        EmailProvider::Capture => Box::new(CaptureClient::new(pool.clone())),
+    };
+    let email_sender = Box::new(MeteredEmailSender::new(
+        email_sender,
+        settings.provider.as_str(),
+    ));
+
+    match settings.provider {
+        EmailProvider::Maildir | EmailProvider::Capture => email_sender,
+        _ => Box::new(RetryingEmailSender::new(
+            email_sender,
+            settings.retry.clone(),
+            &settings.circuit_breaker,
+        )),
+    }
+}
@@ -0,0 +1,72 @@
+use std::borrow::Cow;
+
+pub static SEND_PATH: &str = "/v2/email/outbound-emails";
+pub static SERVICE: &str = "ses";
+/// The mailbox simulator address that accepts emails without delivering them
+pub static SIMULATOR_RECIPIENT: &str = "success@simulator.amazonses.com";
+/// Names the type of an error, e.g. `MessageRejected`
+pub static ERROR_TYPE_HEADER: &str = "x-amzn-ErrorType";
+/// The types of the errors of emails rejected for their content, including their recipients
+pub static REJECTED_ERROR_TYPES: [&str; 2] = ["BadRequestException", "MessageRejected"];
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SendEmailBody<'a> {
+    pub from_email_address: &'a str,
+    #[serde(borrow)]
+    pub destination: Destination<'a>,
+    pub content: Content<'a>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Destination<'a> {
+    #[serde(borrow)]
+    pub to_addresses: Vec<&'a str>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Content<'a> {
+    #[serde(borrow)]
+    pub simple: SimpleContent<'a>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SimpleContent<'a> {
+    #[serde(borrow)]
+    pub subject: Text<'a>,
+    pub body: Body<'a>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<Header>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Body<'a> {
+    #[serde(default, skip_serializing_if = "Option::is_none", borrow)]
+    pub html: Option<Text<'a>>,
+    #[serde(default, skip_serializing_if = "Option::is_none", borrow)]
+    pub text: Option<Text<'a>>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Text<'a> {
+    #[serde(borrow)]
+    pub data: Cow<'a, str>,
+    pub charset: &'a str,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ErrorResponse {
+    pub message: String,
+}
@@ -0,0 +1,395 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use secrecy::Secret;
+
+use crate::domain::EmailAddress;
+use crate::email::sigv4::{self, SigV4Credentials};
+use crate::email::{retry_after, ses, EmailData, EmailSender, SendError};
+use crate::settings::{EmailClientSettings, SesSettings};
+
+/// Sends emails through Amazon SES's v2 API, signing requests with SigV4.
+pub struct SesClient {
+    http_client: reqwest::Client,
+    base_url: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: Secret<String>,
+    from: EmailAddress,
+    sandbox: bool,
+}
+
+impl SesClient {
+    pub fn new(
+        base_url: String,
+        settings: &SesSettings,
+        from: EmailAddress,
+        timeout: Duration,
+        sandbox: bool,
+    ) -> Self {
+        Self {
+            http_client: reqwest::Client::builder().timeout(timeout).build().unwrap(),
+            base_url,
+            region: settings.region.clone(),
+            access_key_id: settings.access_key_id.clone(),
+            secret_access_key: settings.secret_access_key.clone(),
+            from,
+            sandbox,
+        }
+    }
+
+    pub fn from_settings(settings: &EmailClientSettings) -> Result<Self, anyhow::Error> {
+        let ses = settings
+            .ses
+            .as_ref()
+            .context("SES settings are required by the ses provider")?;
+
+        Ok(Self::new(
+            settings.base_url.clone(),
+            ses,
+            settings.sender.clone(),
+            Duration::from_millis(settings.timeout_millis),
+            settings.sandbox,
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for SesClient {
+    async fn send(&self, email: &EmailData) -> Result<(), SendError> {
+        let text = |data| ses::Text {
+            data,
+            charset: "UTF-8",
+        };
+        // SES has no test mode, so sandboxed emails go to its mailbox simulator instead
+        let to = if self.sandbox {
+            ses::SIMULATOR_RECIPIENT
+        } else {
+            email.to.as_ref()
+        };
+
+        let body = ses::SendEmailBody {
+            from_email_address: self.from.as_ref(),
+            destination: ses::Destination {
+                to_addresses: vec![to],
+            },
+            content: ses::Content {
+                simple: ses::SimpleContent {
+                    subject: text(email.subject.as_str().into()),
+                    body: ses::Body {
+                        html: Some(text(email.html_content.as_str().into())),
+                        text: Some(text(email.plain_text())),
+                    },
+                    headers: email
+                        .unsubscribe_headers()
+                        .into_iter()
+                        .map(|(name, value)| ses::Header {
+                            name: name.into(),
+                            value,
+                        })
+                        .collect(),
+                },
+            },
+        };
+        let body = serde_json::to_vec(&body).map_err(|e| SendError::Permanent(e.into()))?;
+
+        let url = reqwest::Url::parse(&format!("{}{}", self.base_url, ses::SEND_PATH))
+            .map_err(|e| SendError::Permanent(e.into()))?;
+        let signature = sigv4::sign(
+            &SigV4Credentials {
+                access_key_id: &self.access_key_id,
+                secret_access_key: &self.secret_access_key,
+                region: &self.region,
+                service: ses::SERVICE,
+            },
+            "POST",
+            &url,
+            &body,
+            chrono::Utc::now(),
+        );
+
+        let response = self
+            .http_client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header("X-Amz-Date", signature.amz_date)
+            .header("Authorization", signature.authorization)
+            .body(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = retry_after(response.headers());
+            // Followed by a URL, e.g. `MessageRejected:http://internal.amazon.com/...`
+            let error_type = response
+                .headers()
+                .get(ses::ERROR_TYPE_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(':').next())
+                .unwrap_or_default()
+                .to_string();
+            let details = response
+                .json::<ses::ErrorResponse>()
+                .await
+                .map(|e| e.message)
+                .unwrap_or_else(|_| "no error details".into());
+
+            // Recipients not verified in the sandbox are named in the message, malformed ones are
+            // not, but they are the only address that the sender did not configure
+            let is_recipient_rejected = ses::REJECTED_ERROR_TYPES.contains(&error_type.as_str())
+                && (details.contains(to)
+                    || (error_type == "BadRequestException"
+                        && details.contains("address")
+                        && !details.contains(self.from.as_ref())));
+            if is_recipient_rejected {
+                return Err(SendError::InvalidRecipient(format!(
+                    "SES responded with {status}, {error_type}: {details}"
+                )));
+            }
+
+            return Err(SendError::from_status("SES", status, retry_after, &details));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use claims::{assert_err, assert_matches, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::{Fake, Faker};
+    use reqwest::{Method, StatusCode};
+    use secrecy::Secret;
+    use wiremock::matchers::{any, header, header_exists, method, path};
+    use wiremock::{Match, Mock, MockServer, Request, ResponseTemplate};
+
+    use crate::domain::EmailAddress;
+    use crate::email::test_helpers::{
+        assert_send_fails_transiently_on_server_errors, assert_send_times_out, fake_email_data,
+        BodyMatcher,
+    };
+    use crate::email::{ses, EmailSender, SendError, SesClient};
+    use crate::settings::SesSettings;
+
+    struct SigV4AuthorizationMatcher;
+
+    impl Match for SigV4AuthorizationMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            // The header is split on commas into several values
+            let Some((_, values)) = request
+                .headers
+                .iter()
+                .find(|(name, _)| name.as_str().eq_ignore_ascii_case("authorization"))
+            else {
+                return false;
+            };
+            let authorization: Vec<_> = values.iter().map(|v| v.as_str().trim()).collect();
+
+            authorization.len() == 3
+                && authorization[0].starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/")
+                && authorization[0].ends_with("/eu-west-1/ses/aws4_request")
+                && authorization[1] == "SignedHeaders=host;x-amz-date"
+                && authorization[2].starts_with("Signature=")
+        }
+    }
+
+    struct RecipientMatcher(&'static str);
+
+    impl Match for RecipientMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            serde_json::from_slice::<ses::SendEmailBody>(&request.body)
+                .map(|body| body.destination.to_addresses == vec![self.0])
+                .unwrap_or(false)
+        }
+    }
+
+    fn email_client(base_url: String, sandbox: bool) -> SesClient {
+        SesClient::new(
+            base_url,
+            &SesSettings {
+                region: "eu-west-1".into(),
+                access_key_id: "AKIDEXAMPLE".into(),
+                secret_access_key: Secret::new(Faker.fake()),
+            },
+            EmailAddress::parse(SafeEmail().fake()).unwrap(),
+            Duration::from_millis(200),
+            sandbox,
+        )
+    }
+
+    #[tokio::test]
+    async fn send_email_fires_a_signed_request_to_server() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), false);
+
+        Mock::given(method(Method::POST))
+            .and(path(ses::SEND_PATH))
+            .and(SigV4AuthorizationMatcher)
+            .and(header_exists("X-Amz-Date"))
+            .and(header("Content-Type", "application/json"))
+            .and(BodyMatcher(|body| {
+                serde_json::from_slice::<ses::SendEmailBody>(body).is_ok()
+            }))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_ok!(result);
+    }
+
+    #[tokio::test]
+    async fn send_email_goes_to_the_mailbox_simulator_in_sandbox_mode() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), true);
+
+        Mock::given(RecipientMatcher(ses::SIMULATOR_RECIPIENT))
+            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_ok!(result);
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_the_error_returned_by_the_server() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), false);
+
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(StatusCode::BAD_REQUEST)
+                    .insert_header(ses::ERROR_TYPE_HEADER, "NotFoundException")
+                    .set_body_json(
+                        serde_json::json!({"message": "Configuration set does not exist."}),
+                    ),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        let error = assert_err!(result);
+        assert_matches!(&error, SendError::Permanent(_));
+        assert!(error.to_string().contains("does not exist"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_unverified_recipients_as_invalid() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), false);
+        let email = fake_email_data();
+
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(StatusCode::BAD_REQUEST)
+                    .insert_header(
+                        ses::ERROR_TYPE_HEADER,
+                        "MessageRejected:http://internal.amazon.com/coral/com.amazonaws.sesv2/",
+                    )
+                    .set_body_json(serde_json::json!({
+                        "message": format!(
+                            "Email address is not verified. The following identities failed \
+                            the check in region EU-WEST-1: {}",
+                            email.to.as_ref()
+                        )
+                    })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&email).await;
+
+        // Then
+        let error = assert_err!(result);
+        assert_matches!(&error, SendError::InvalidRecipient(_));
+        assert!(error.to_string().contains("not verified"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_malformed_recipients_as_invalid() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), false);
+
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(StatusCode::BAD_REQUEST)
+                    .insert_header(ses::ERROR_TYPE_HEADER, "BadRequestException")
+                    .set_body_json(serde_json::json!({
+                        "message": "Local address contains control or whitespace"
+                    })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_matches!(assert_err!(result), SendError::InvalidRecipient(_));
+    }
+
+    #[tokio::test]
+    async fn send_email_does_not_report_an_unverified_sender_as_an_invalid_recipient() {
+        // Given
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), false);
+
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(StatusCode::BAD_REQUEST)
+                    .insert_header(ses::ERROR_TYPE_HEADER, "MessageRejected")
+                    .set_body_json(serde_json::json!({
+                        "message": format!(
+                            "Email address is not verified. The following identities failed \
+                            the check in region EU-WEST-1: {}",
+                            email_client.from.as_ref()
+                        )
+                    })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_matches!(assert_err!(result), SendError::Permanent(_));
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_transiently_if_the_server_fails() {
+        assert_send_fails_transiently_on_server_errors(|base_url| email_client(base_url, false))
+            .await;
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        assert_send_times_out(|base_url| email_client(base_url, false)).await;
+    }
+}
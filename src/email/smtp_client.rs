@@ -0,0 +1,296 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use lettre::transport::smtp::authentication::{Credentials, Mechanism};
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::transport::smtp::PoolConfig;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use secrecy::ExposeSecret;
+
+use crate::domain::EmailAddress;
+use crate::email::mime::to_message;
+use crate::email::{EmailData, EmailSender, SendError};
+use crate::settings::{EmailClientSettings, SmtpSettings, SmtpTls};
+
+/// Sends emails as MIME messages through an SMTP server.
+pub struct SmtpClient {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: EmailAddress,
+    timeout: Duration,
+}
+
+impl SmtpClient {
+    pub fn new(
+        settings: &SmtpSettings,
+        from: EmailAddress,
+        timeout: Duration,
+    ) -> Result<Self, anyhow::Error> {
+        let tls = match settings.tls {
+            SmtpTls::None => Tls::None,
+            SmtpTls::StartTls => Tls::Required(TlsParameters::new(settings.host.clone())?),
+            SmtpTls::Implicit => Tls::Wrapper(TlsParameters::new(settings.host.clone())?),
+        };
+
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host)
+            .port(settings.port)
+            .tls(tls)
+            .timeout(Some(timeout))
+            .pool_config(PoolConfig::new().max_size(settings.max_pool_size));
+
+        if let Some(username) = &settings.username {
+            let password = settings
+                .password
+                .as_ref()
+                .context("An SMTP password is required with a username")?;
+
+            builder = builder
+                .credentials(Credentials::new(
+                    username.clone(),
+                    password.expose_secret().clone(),
+                ))
+                .authentication(vec![Mechanism::Plain, Mechanism::Login]);
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+            timeout,
+        })
+    }
+
+    pub fn from_settings(settings: &EmailClientSettings) -> Result<Self, anyhow::Error> {
+        Self::new(
+            settings
+                .smtp
+                .as_ref()
+                .context("SMTP settings are required by the smtp provider")?,
+            settings.sender.clone(),
+            Duration::from_millis(settings.timeout_millis),
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for SmtpClient {
+    async fn send(&self, email: &EmailData) -> Result<(), SendError> {
+        let message = to_message(&self.from, email).map_err(SendError::Permanent)?;
+
+        // The transport's timeout does not cover a server that never greets
+        match tokio::time::timeout(self.timeout, self.transport.send(message)).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => Err(to_send_error(e)),
+            Err(e) => Err(SendError::Transient(
+                anyhow::Error::new(e).context("Timed out sending the email over SMTP"),
+            )),
+        }
+    }
+}
+
+/// Classifies an SMTP error by its reply code (RFC 5321), e.g. 550 for an unknown mailbox.
+fn to_send_error(e: lettre::transport::smtp::Error) -> SendError {
+    let code = e.status().map(|code| code.to_string());
+
+    match code.as_deref() {
+        Some("550" | "551" | "553") => SendError::InvalidRecipient(e.to_string()),
+        Some("530" | "534" | "535") => SendError::AuthFailure(e.to_string()),
+        _ if e.is_permanent() || e.is_client() => SendError::Permanent(
+            anyhow::Error::new(e).context("Failed to send the email over SMTP"),
+        ),
+        // Connection, network and transient (4xx) errors
+        _ => SendError::Transient(
+            anyhow::Error::new(e).context("Failed to send the email over SMTP"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use claims::{assert_err, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::Fake;
+    use secrecy::Secret;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    use crate::domain::EmailAddress;
+    use crate::email::test_helpers::fake_email_data;
+    use crate::email::{EmailData, EmailSender, SmtpClient};
+    use crate::settings::{SmtpSettings, SmtpTls};
+
+    /// A local SMTP sink recording the commands and messages it receives.
+    #[derive(Clone, Default)]
+    struct SmtpSink {
+        commands: Arc<Mutex<Vec<String>>>,
+        messages: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl SmtpSink {
+        async fn start(&self) -> u16 {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let sink = self.clone();
+
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(sink.clone().serve(stream));
+                }
+            });
+
+            port
+        }
+
+        async fn serve(self, stream: tokio::net::TcpStream) {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+
+            writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                self.commands.lock().unwrap().push(line.clone());
+                let command = line.to_uppercase();
+
+                let reply: &[u8] = if command.starts_with("EHLO") {
+                    b"250-localhost\r\n250 AUTH PLAIN LOGIN\r\n"
+                } else if command.starts_with("AUTH") {
+                    b"235 Authentication succeeded\r\n"
+                } else if command.starts_with("DATA") {
+                    writer.write_all(b"354 End data with .\r\n").await.unwrap();
+
+                    let mut message = String::new();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        if line == "." {
+                            break;
+                        }
+                        message.push_str(&line);
+                        message.push('\n');
+                    }
+                    self.messages.lock().unwrap().push(message);
+
+                    b"250 Queued\r\n"
+                } else if command.starts_with("QUIT") {
+                    writer.write_all(b"221 Bye\r\n").await.unwrap();
+                    return;
+                } else {
+                    b"250 OK\r\n"
+                };
+
+                writer.write_all(reply).await.unwrap();
+            }
+        }
+    }
+
+    fn smtp_settings(port: u16) -> SmtpSettings {
+        SmtpSettings {
+            host: "127.0.0.1".into(),
+            port,
+            tls: SmtpTls::None,
+            username: None,
+            password: None,
+            max_pool_size: 1,
+        }
+    }
+
+    fn smtp_client(settings: &SmtpSettings) -> SmtpClient {
+        SmtpClient::new(
+            settings,
+            EmailAddress::parse(SafeEmail().fake()).unwrap(),
+            Duration::from_millis(200),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn send_email_delivers_a_mime_message_to_the_server() {
+        // Given
+        let sink = SmtpSink::default();
+        let port = sink.start().await;
+        let email_client = smtp_client(&smtp_settings(port));
+        let email = fake_email_data();
+
+        // When
+        let result = email_client.send(&email).await;
+
+        // Then
+        assert_ok!(result);
+
+        let commands = sink.commands.lock().unwrap().clone();
+        assert!(commands
+            .iter()
+            .any(|c| c == &format!("RCPT TO:<{}>", email.to.as_ref())));
+
+        let messages = sink.messages.lock().unwrap().clone();
+        assert_eq!(1, messages.len());
+        assert!(messages[0].contains(&format!("Subject: {}", email.subject)));
+        assert!(messages[0].contains("Content-Type: text/plain"));
+        assert!(messages[0].contains("Content-Type: text/html"));
+    }
+
+    #[tokio::test]
+    async fn send_email_adds_the_unsubscribe_headers() {
+        // Given
+        let sink = SmtpSink::default();
+        let port = sink.start().await;
+        let email_client = smtp_client(&smtp_settings(port));
+        let unsubscribe_url = reqwest::Url::parse("https://example.com/unsubscribe").unwrap();
+        let email = EmailData {
+            unsubscribe_url: Some(unsubscribe_url.clone()),
+            ..fake_email_data()
+        };
+
+        // When
+        email_client.send(&email).await.unwrap();
+
+        // Then
+        let message = sink.messages.lock().unwrap()[0].clone();
+        assert!(message.contains(&format!("List-Unsubscribe: <{unsubscribe_url}>")));
+        assert!(message.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+    }
+
+    #[tokio::test]
+    async fn send_email_authenticates_when_credentials_are_set() {
+        // Given
+        let sink = SmtpSink::default();
+        let port = sink.start().await;
+        let email_client = smtp_client(&SmtpSettings {
+            username: Some("user".into()),
+            password: Some(Secret::new("password".into())),
+            ..smtp_settings(port)
+        });
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_ok!(result);
+        assert!(sink
+            .commands
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|c| c.starts_with("AUTH PLAIN")));
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        // Given
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Accepts connections without ever greeting
+        let _server = tokio::spawn(async move {
+            let mut streams = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+        let email_client = smtp_client(&smtp_settings(port));
+
+        // When
+        let result = email_client.send(&fake_email_data()).await;
+
+        // Then
+        assert_err!(result);
+    }
+}
@@ -0,0 +1,334 @@
+use anyhow::Context;
+use tera::Tera;
+
+use crate::email::html_to_text::normalize_lines;
+use crate::html::escape_html;
+use crate::settings::EmailTemplatesSettings;
+
+/// An email rendered from the `{locale}/{NAME}.subject.txt`, `{locale}/{NAME}.html` and, if
+/// present, `{locale}/{NAME}.txt` templates, with the fields of the implementor as variables.
+///
+/// HTML templates are auto-escaped, and usually extend their locale's `layout.html`.
+pub trait EmailTemplate: serde::Serialize {
+    const NAME: &'static str;
+
+    /// Rendered at startup, so that broken templates fail the startup instead of the first send.
+    fn example() -> Self;
+}
+
+#[derive(serde::Serialize)]
+pub struct ConfirmationEmail {
+    pub subscriber_name: String,
+    pub confirmation_link: String,
+    pub unsubscribe_url: String,
+}
+
+impl EmailTemplate for ConfirmationEmail {
+    const NAME: &'static str = "confirmation";
+
+    fn example() -> Self {
+        Self {
+            subscriber_name: "Ursula Le Guin".into(),
+            confirmation_link: "https://example.com/subscriptions/confirm?token=token".into(),
+            unsubscribe_url: "https://example.com/subscriptions/unsubscribe?token=token".into(),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct NewsletterEmail {
+    pub title: String,
+    /// Written by an administrator, so it is not escaped
+    pub html_content: String,
+    pub text_content: String,
+    pub unsubscribe_url: String,
+}
+
+impl EmailTemplate for NewsletterEmail {
+    const NAME: &'static str = "newsletter";
+
+    fn example() -> Self {
+        Self {
+            title: "Newsletter title".into(),
+            html_content: "<p>Newsletter body as HTML</p>".into(),
+            text_content: "Newsletter body as plain text".into(),
+            unsubscribe_url: "https://example.com/subscriptions/unsubscribe?token=token".into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html_content: String,
+    /// Absent when the template has no plain-text version
+    pub text_content: Option<String>,
+}
+
+/// The email templates, compiled once at startup.
+#[derive(Debug)]
+pub struct EmailTemplates {
+    tera: Tera,
+    locales: Vec<String>,
+    default_locale: String,
+}
+
+impl EmailTemplates {
+    /// Compiles the templates in every locale directory, and renders every template to check them.
+    pub fn load(settings: &EmailTemplatesSettings) -> Result<Self, anyhow::Error> {
+        let mut tera = Tera::new(&format!("{}/**/*", settings.dir)).with_context(|| {
+            format!("Failed to compile the email templates in {}", settings.dir)
+        })?;
+        tera.set_escape_fn(escape_html);
+
+        let mut locales: Vec<String> = tera
+            .get_template_names()
+            .filter_map(|name| name.split_once('/').map(|(locale, _)| locale.to_string()))
+            .collect();
+        locales.sort();
+        locales.dedup();
+
+        if !locales.contains(&settings.default_locale) {
+            anyhow::bail!(
+                "There are no email templates for the default locale {} in {}",
+                settings.default_locale,
+                settings.dir
+            );
+        }
+
+        let templates = Self {
+            tera,
+            locales,
+            default_locale: settings.default_locale.clone(),
+        };
+
+        for locale in &templates.locales {
+            templates.render(locale, &ConfirmationEmail::example())?;
+            templates.render(locale, &NewsletterEmail::example())?;
+        }
+
+        Ok(templates)
+    }
+
+    pub fn default_locale(&self) -> &str {
+        &self.default_locale
+    }
+
+    /// The first of the preferred languages, e.g. from `Accept-Language`, with templates.
+    ///
+    /// A language with a region, e.g. `en-GB`, falls back to its primary language, e.g. `en`.
+    pub fn negotiate_locale<'a>(&self, preferred: impl IntoIterator<Item = &'a str>) -> &str {
+        preferred
+            .into_iter()
+            .find_map(|tag| {
+                let tag = tag.to_lowercase();
+                let primary = tag.split('-').next().unwrap_or_default();
+
+                self.locales
+                    .iter()
+                    .find(|l| l.to_lowercase() == tag)
+                    .or_else(|| self.locales.iter().find(|l| l.to_lowercase() == primary))
+            })
+            .unwrap_or(&self.default_locale)
+    }
+
+    /// Renders the email in the locale, or in the default locale if it has no such template.
+    pub fn render<T: EmailTemplate>(
+        &self,
+        locale: &str,
+        email: &T,
+    ) -> Result<RenderedEmail, anyhow::Error> {
+        let name = |extension| format!("{locale}/{}.{extension}", T::NAME);
+        let locale = if self.has_template(&name("html")) {
+            locale
+        } else {
+            &self.default_locale
+        };
+        let name = |extension| format!("{locale}/{}.{extension}", T::NAME);
+        let render = |name: String, context: &tera::Context| {
+            self.tera
+                .render(&name, context)
+                .with_context(|| format!("Failed to render the {name} email template"))
+        };
+
+        let mut context = tera::Context::from_serialize(email)
+            .with_context(|| format!("Failed to build the {} email context", T::NAME))?;
+
+        let subject = render(name("subject.txt"), &context)?.trim().to_string();
+        context.insert("subject", &subject);
+
+        let html_content = render(name("html"), &context)?;
+        let text_content = self
+            .has_template(&name("txt"))
+            .then(|| render(name("txt"), &context))
+            .transpose()?
+            .map(|text| normalize_lines(&text));
+
+        Ok(RenderedEmail {
+            subject,
+            html_content,
+            text_content,
+        })
+    }
+
+    fn has_template(&self, name: &str) -> bool {
+        self.tera.get_template_names().any(|n| n == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use claims::assert_err;
+    use uuid::Uuid;
+
+    use crate::email::{ConfirmationEmail, EmailTemplate, EmailTemplates, NewsletterEmail};
+    use crate::settings::EmailTemplatesSettings;
+
+    fn repo_templates() -> EmailTemplates {
+        EmailTemplates::load(&EmailTemplatesSettings {
+            dir: "templates/emails".into(),
+            default_locale: "en".into(),
+        })
+        .unwrap()
+    }
+
+    /// A copy of the repository's templates in the temporary directory, removed when dropped.
+    struct TestTemplatesDir(PathBuf);
+
+    impl TestTemplatesDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("zero2prod-templates-{}", Uuid::new_v4()));
+            std::fs::create_dir_all(dir.join("en")).unwrap();
+            for entry in std::fs::read_dir("templates/emails/en").unwrap() {
+                let path = entry.unwrap().path();
+                std::fs::copy(&path, dir.join("en").join(path.file_name().unwrap())).unwrap();
+            }
+
+            Self(dir)
+        }
+
+        fn write(&self, name: &str, content: &str) {
+            let path = self.0.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+
+        fn load(&self) -> Result<EmailTemplates, anyhow::Error> {
+            EmailTemplates::load(&EmailTemplatesSettings {
+                dir: self.0.to_str().unwrap().into(),
+                default_locale: "en".into(),
+            })
+        }
+    }
+
+    impl Drop for TestTemplatesDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn the_confirmation_email_is_rendered_in_the_layout() {
+        let email = ConfirmationEmail::example();
+
+        let rendered = repo_templates().render("en", &email).unwrap();
+
+        assert_eq!("Welcome!", rendered.subject);
+        assert!(rendered
+            .html_content
+            .contains(&format!(r#"<a href="{}">"#, email.confirmation_link)));
+        assert!(rendered.html_content.contains(&format!(
+            r#"<a href="{}">Unsubscribe</a>"#,
+            email.unsubscribe_url
+        )));
+
+        let text_content = rendered.text_content.unwrap();
+        assert!(text_content.contains(&email.confirmation_link));
+        assert!(text_content.contains(&format!("Unsubscribe: {}", email.unsubscribe_url)));
+    }
+
+    #[test]
+    fn variables_are_escaped_in_html_but_not_in_text() {
+        let email = ConfirmationEmail {
+            subscriber_name: "Tom & Jerry <b>".into(),
+            ..ConfirmationEmail::example()
+        };
+
+        let rendered = repo_templates().render("en", &email).unwrap();
+
+        assert!(rendered.html_content.contains("Tom &amp; Jerry &lt;b&gt;"));
+        assert!(!rendered.html_content.contains("<b>"));
+        assert!(rendered.text_content.unwrap().contains("Tom & Jerry <b>"));
+    }
+
+    #[test]
+    fn the_newsletter_html_content_is_not_escaped() {
+        let email = NewsletterEmail::example();
+
+        let rendered = repo_templates().render("en", &email).unwrap();
+
+        assert_eq!(email.title, rendered.subject);
+        assert!(rendered.html_content.contains(&email.html_content));
+    }
+
+    #[test]
+    fn templates_are_rendered_in_the_negotiated_locale() {
+        let dir = TestTemplatesDir::new();
+        dir.write("fr/confirmation.subject.txt", "Bienvenue !");
+        dir.write("fr/confirmation.html", "Bonjour {{ subscriber_name }}");
+        let templates = dir.load().unwrap();
+
+        let locale = templates.negotiate_locale(["de", "fr-CH", "en"]);
+        let rendered = templates
+            .render(locale, &ConfirmationEmail::example())
+            .unwrap();
+
+        assert_eq!("fr", locale);
+        assert_eq!("Bienvenue !", rendered.subject);
+        assert_eq!(None, rendered.text_content);
+    }
+
+    #[test]
+    fn unknown_locales_and_missing_templates_fall_back_to_the_default_locale() {
+        let dir = TestTemplatesDir::new();
+        dir.write("fr/confirmation.subject.txt", "Bienvenue !");
+        dir.write("fr/confirmation.html", "Bonjour {{ subscriber_name }}");
+        let templates = dir.load().unwrap();
+
+        assert_eq!("en", templates.negotiate_locale(["de"]));
+        assert_eq!(
+            "Newsletter title",
+            templates
+                .render("fr", &NewsletterEmail::example())
+                .unwrap()
+                .subject
+        );
+    }
+
+    #[test]
+    fn loading_fails_if_a_template_is_broken() {
+        let cases = [
+            ("en/confirmation.html", "{% if %}"),
+            ("en/confirmation.html", "{{ unknown_variable }}"),
+            ("en/confirmation.html", r#"{% extends "en/missing.html" %}"#),
+            ("fr/confirmation.subject.txt", "{{ subscriber_name"),
+        ];
+
+        for (name, content) in cases {
+            let dir = TestTemplatesDir::new();
+            dir.write(name, content);
+
+            assert_err!(dir.load(), "{name}: {content}");
+        }
+    }
+
+    #[test]
+    fn loading_fails_without_templates_for_the_default_locale() {
+        let dir = TestTemplatesDir::new();
+        std::fs::rename(dir.0.join("en"), dir.0.join("fr")).unwrap();
+
+        assert_err!(dir.load());
+    }
+}
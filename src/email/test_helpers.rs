@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use claims::assert_matches;
+use fake::faker::internet::en::SafeEmail;
+use fake::faker::lorem::en::{Paragraph, Sentence};
+use fake::Fake;
+use reqwest::StatusCode;
+use wiremock::matchers::any;
+use wiremock::{Match, Mock, MockServer, Request, ResponseTemplate};
+
+use crate::domain::EmailAddress;
+use crate::email::{EmailData, EmailSender, SendError};
+
+pub fn fake_email_data() -> EmailData {
+    EmailData {
+        to: EmailAddress::parse(SafeEmail().fake()).unwrap(),
+        subject: Sentence(1..2).fake(),
+        html_content: Paragraph(1..10).fake(),
+        text_content: None,
+        unsubscribe_url: None,
+    }
+}
+
+/// Matches the requests whose body passes the check, e.g. that it is the provider's request body.
+pub struct BodyMatcher(pub fn(&[u8]) -> bool);
+
+impl Match for BodyMatcher {
+    fn matches(&self, request: &Request) -> bool {
+        (self.0)(&request.body)
+    }
+}
+
+/// Checks that a client of an HTTP API, with a timeout well below the server's delay, gives up on
+/// a slow server so that the email is retried.
+pub async fn assert_send_times_out<S: EmailSender>(email_client: impl FnOnce(String) -> S) {
+    // Given
+    let mock_server = MockServer::start().await;
+    let email_client = email_client(mock_server.uri());
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(StatusCode::OK).set_delay(Duration::from_secs(180)))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // When
+    let result = email_client.send(&fake_email_data()).await;
+
+    // Then
+    assert_matches!(result, Err(SendError::Transient(_)));
+}
+
+/// Checks that a client of an HTTP API reports the server's errors as transient.
+pub async fn assert_send_fails_transiently_on_server_errors<S: EmailSender>(
+    email_client: impl FnOnce(String) -> S,
+) {
+    // Given
+    let mock_server = MockServer::start().await;
+    let email_client = email_client(mock_server.uri());
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(StatusCode::INTERNAL_SERVER_ERROR))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // When
+    let result = email_client.send(&fake_email_data()).await;
+
+    // Then
+    assert_matches!(result, Err(SendError::Transient(_)));
+}
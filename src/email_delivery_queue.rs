@@ -0,0 +1,245 @@
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::domain::EmailAddress;
+use crate::email::EmailData;
+
+/// Where each recipient's unsubscribe URL goes in the content of a newsletter issue.
+pub const UNSUBSCRIBE_URL_KEY: &str = "%unsubscribe_url%";
+
+pub struct EmailDeliveryTask {
+    pub id: Uuid,
+    pub recipient: String,
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: Option<String>,
+    pub unsubscribe_url: Option<String>,
+    pub n_attempts: i32,
+    /// Set for the deliveries of a newsletter issue, which are sent in batches
+    pub newsletter_issue_id: Option<Uuid>,
+}
+
+#[tracing::instrument(name = "Enqueuing email for delivery", skip_all)]
+pub async fn enqueue_email<'e>(
+    executor: impl PgExecutor<'e>,
+    email: &EmailData,
+) -> Result<Uuid, sqlx::Error> {
+    let task_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO email_delivery_queue (
+            id, recipient, subject, html_content, text_content, unsubscribe_url,
+            n_attempts, execute_after, created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, 0, now(), now())
+        "#,
+        task_id,
+        email.to.as_ref(),
+        email.subject,
+        email.html_content,
+        email.text_content,
+        email.unsubscribe_url.as_ref().map(|url| url.as_str()),
+    )
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to enqueue email: {}", e);
+        e
+    })?;
+
+    Ok(task_id)
+}
+
+/// Stores the content of a newsletter issue once, for [`enqueue_newsletter_issue`] to deliver.
+#[tracing::instrument(name = "Inserting newsletter issue", skip_all)]
+pub async fn insert_newsletter_issue<'e>(
+    executor: impl PgExecutor<'e>,
+    subject: &str,
+    html_content: &str,
+    text_content: Option<&str>,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (id, subject, html_content, text_content, published_at)
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        newsletter_issue_id,
+        subject,
+        html_content,
+        text_content,
+    )
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to insert newsletter issue: {}", e);
+        e
+    })?;
+
+    Ok(newsletter_issue_id)
+}
+
+/// Enqueues a delivery of the newsletter issue to each recipient, with their unsubscribe URL.
+#[tracing::instrument(
+    name = "Enqueuing newsletter issue for delivery",
+    skip_all,
+    fields(n_recipients = recipients.len())
+)]
+pub async fn enqueue_newsletter_issue<'e>(
+    executor: impl PgExecutor<'e>,
+    newsletter_issue_id: Uuid,
+    recipients: &[(EmailAddress, reqwest::Url)],
+) -> Result<(), sqlx::Error> {
+    let task_ids = recipients
+        .iter()
+        .map(|_| Uuid::new_v4())
+        .collect::<Vec<_>>();
+    let (emails, unsubscribe_urls): (Vec<_>, Vec<_>) = recipients
+        .iter()
+        .map(|(email, unsubscribe_url)| (email.as_ref().to_string(), unsubscribe_url.to_string()))
+        .unzip();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO email_delivery_queue (
+            id, recipient, unsubscribe_url, newsletter_issue_id,
+            n_attempts, execute_after, created_at
+        )
+        SELECT id, recipient, unsubscribe_url, $4, 0, now(), now()
+        FROM UNNEST($1::uuid[], $2::text[], $3::text[]) AS t (id, recipient, unsubscribe_url)
+        "#,
+        &task_ids,
+        &emails,
+        &unsubscribe_urls,
+        newsletter_issue_id,
+    )
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to enqueue newsletter issue: {}", e);
+        e
+    })?;
+
+    Ok(())
+}
+
+/// Claims the next due task, skipping the ones being claimed by other workers, along with up to
+/// `max_batch_size` due tasks in total of the same newsletter issue.
+///
+/// The tasks are leased rather than kept locked while they are sent: they are not due again
+/// until `lease_expires_at`, by which time they should have been [deleted](delete_tasks) or
+/// [rescheduled](reschedule_task). The tasks of a worker that stopped are retried then.
+#[tracing::instrument(name = "Dequeuing email delivery tasks", skip_all)]
+pub async fn dequeue_tasks(
+    pool: &PgPool,
+    max_batch_size: usize,
+    lease_expires_at: chrono::DateTime<chrono::Utc>,
+) -> Result<Option<Vec<EmailDeliveryTask>>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    let Some(task) = sqlx::query_as!(
+        EmailDeliveryTask,
+        r#"
+        SELECT
+            q.id,
+            q.recipient,
+            COALESCE(q.subject, i.subject) AS "subject!",
+            COALESCE(q.html_content, i.html_content) AS "html_content!",
+            COALESCE(q.text_content, i.text_content) AS text_content,
+            q.unsubscribe_url,
+            q.n_attempts,
+            q.newsletter_issue_id
+        FROM email_delivery_queue q
+        LEFT JOIN newsletter_issues i ON i.id = q.newsletter_issue_id
+        WHERE q.execute_after <= now()
+        ORDER BY q.execute_after
+        FOR UPDATE OF q
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut transaction)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let mut tasks = vec![task];
+
+    if let Some(newsletter_issue_id) = tasks[0].newsletter_issue_id {
+        let others = sqlx::query_as!(
+            EmailDeliveryTask,
+            r#"
+        SELECT
+            q.id,
+            q.recipient,
+            i.subject,
+            i.html_content,
+            i.text_content,
+            q.unsubscribe_url,
+            q.n_attempts,
+            q.newsletter_issue_id
+        FROM email_delivery_queue q
+        JOIN newsletter_issues i ON i.id = q.newsletter_issue_id
+        WHERE q.newsletter_issue_id = $1 AND q.id <> $2 AND q.execute_after <= now()
+        ORDER BY q.execute_after
+        FOR UPDATE OF q
+        SKIP LOCKED
+        LIMIT $3
+        "#,
+            newsletter_issue_id,
+            tasks[0].id,
+            max_batch_size.saturating_sub(1) as i64,
+        )
+        .fetch_all(&mut transaction)
+        .await?;
+        tasks.extend(others);
+    }
+
+    let task_ids = tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+    sqlx::query!(
+        "UPDATE email_delivery_queue SET execute_after = $2 WHERE id = ANY($1)",
+        &task_ids,
+        lease_expires_at
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(Some(tasks))
+}
+
+#[tracing::instrument(name = "Deleting email delivery tasks", skip_all)]
+pub async fn delete_tasks<'e>(
+    executor: impl PgExecutor<'e>,
+    task_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM email_delivery_queue WHERE id = ANY($1)",
+        task_ids
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Rescheduling email delivery task", skip_all)]
+pub async fn reschedule_task<'e>(
+    executor: impl PgExecutor<'e>,
+    task_id: Uuid,
+    n_attempts: i32,
+    execute_after: chrono::DateTime<chrono::Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE email_delivery_queue SET n_attempts = $2, execute_after = $3 WHERE id = $1",
+        task_id,
+        n_attempts,
+        execute_after
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
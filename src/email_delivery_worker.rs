@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Context;
+use sqlx::PgPool;
+use tracing::{field::display, Span};
+
+use crate::domain::EmailAddress;
+use crate::email::{BatchEmailData, BatchRecipient, EmailData, EmailSender, SendError};
+use crate::email_delivery_queue::{
+    delete_tasks, dequeue_tasks, reschedule_task, EmailDeliveryTask, UNSUBSCRIBE_URL_KEY,
+};
+use crate::settings::EmailDeliverySettings;
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+pub async fn run_worker_until_stopped(
+    pool: PgPool,
+    email_sender: Box<dyn EmailSender>,
+    settings: EmailDeliverySettings,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_execute_task(&pool, email_sender.as_ref(), &settings).await {
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_millis(settings.poll_interval_millis)).await;
+            }
+            Err(_) => tokio::time::sleep(Duration::from_secs(1)).await,
+        }
+    }
+}
+
+/// Delivers the next due email, or the next batch of a newsletter issue.
+#[tracing::instrument(
+    name = "Executing email delivery task",
+    skip_all,
+    fields(task_id, n_tasks, n_attempts),
+    err
+)]
+pub async fn try_execute_task(
+    pool: &PgPool,
+    email_sender: &dyn EmailSender,
+    settings: &EmailDeliverySettings,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let lease_expires_at =
+        chrono::Utc::now() + chrono::Duration::seconds(settings.lease_secs as i64);
+    let Some(tasks) = dequeue_tasks(pool, email_sender.max_batch_size(), lease_expires_at)
+        .await
+        .context("Failed to dequeue tasks")?
+    else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+
+    Span::current()
+        .record("task_id", display(tasks[0].id))
+        .record("n_tasks", tasks.len())
+        .record("n_attempts", tasks[0].n_attempts);
+
+    let mut deliveries = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        match batch_recipient(&task) {
+            Ok(recipient) => deliveries.push((task, recipient)),
+            // Sending it again would fail the same way
+            Err(e) => {
+                tracing::error!("Dropping an email that cannot be delivered: {:#}", e);
+                delete_tasks(pool, &[task.id])
+                    .await
+                    .context("Failed to delete task")?;
+            }
+        }
+    }
+
+    if !deliveries.is_empty() {
+        deliver(pool, deliveries, email_sender, settings).await?;
+    }
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+/// The recipient of the task, with their unsubscribe URL substituted in the content.
+fn batch_recipient(task: &EmailDeliveryTask) -> Result<BatchRecipient, anyhow::Error> {
+    let to = EmailAddress::parse(task.recipient.clone())
+        .map_err(|e| anyhow::anyhow!("Invalid recipient: {}", e))?;
+    let unsubscribe_url = task
+        .unsubscribe_url
+        .as_deref()
+        .map(reqwest::Url::parse)
+        .transpose()
+        .context("Invalid unsubscribe URL")?;
+
+    Ok(BatchRecipient {
+        to,
+        substitutions: unsubscribe_url
+            .iter()
+            .map(|url| (UNSUBSCRIBE_URL_KEY.to_string(), url.to_string()))
+            .collect::<HashMap<_, _>>(),
+        unsubscribe_url,
+    })
+}
+
+/// Sends the emails of the tasks, as a batch if they are of a newsletter issue.
+async fn deliver(
+    pool: &PgPool,
+    deliveries: Vec<(EmailDeliveryTask, BatchRecipient)>,
+    email_sender: &dyn EmailSender,
+    settings: &EmailDeliverySettings,
+) -> Result<(), anyhow::Error> {
+    let (tasks, recipients): (Vec<_>, Vec<_>) = deliveries.into_iter().unzip();
+    let batch = BatchEmailData {
+        subject: tasks[0].subject.clone(),
+        html_content: tasks[0].html_content.clone(),
+        text_content: tasks[0].text_content.clone(),
+        recipients,
+    };
+
+    let result = if tasks[0].newsletter_issue_id.is_some() {
+        email_sender.send_batch(&batch).await
+    } else {
+        let email = EmailData {
+            to: batch.recipients[0].to.clone(),
+            subject: batch.subject.clone(),
+            html_content: batch.html_content.clone(),
+            text_content: batch.text_content.clone(),
+            unsubscribe_url: batch.recipients[0].unsubscribe_url.clone(),
+        };
+        email_sender.send(&email).await
+    };
+
+    match result {
+        // The provider rejects a request for any of its recipients, so they are sent one at a
+        // time to deliver the others
+        Err(SendError::InvalidRecipient(_)) if tasks.len() > 1 => {
+            for (task, email) in tasks.iter().zip(batch.personalized()) {
+                let result = email_sender.send(&email).await;
+                complete_tasks(pool, std::slice::from_ref(task), result, settings).await?;
+            }
+
+            Ok(())
+        }
+        result => complete_tasks(pool, &tasks, result, settings).await,
+    }
+}
+
+/// Deletes the tasks that were delivered or cannot be, and reschedules the others.
+async fn complete_tasks(
+    pool: &PgPool,
+    tasks: &[EmailDeliveryTask],
+    result: Result<(), SendError>,
+    settings: &EmailDeliverySettings,
+) -> Result<(), anyhow::Error> {
+    let task_ids = tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+
+    let e = match result {
+        Ok(()) => {
+            return delete_tasks(pool, &task_ids)
+                .await
+                .context("Failed to delete tasks");
+        }
+        // Sending them again would fail the same way
+        Err(e @ (SendError::InvalidRecipient(_) | SendError::Permanent(_))) => {
+            tracing::error!(
+                "Dropping {} email(s) that cannot be delivered: {}",
+                tasks.len(),
+                e
+            );
+            return delete_tasks(pool, &task_ids)
+                .await
+                .context("Failed to delete tasks");
+        }
+        Err(e) => e,
+    };
+
+    // Nothing was attempted, so the attempts are not counted
+    if let SendError::CircuitOpen { retry_after } = e {
+        let delay = retry_after.max(Duration::from_millis(settings.base_backoff_millis));
+        tracing::warn!(
+            "Not delivering {} email(s) while the circuit is open, retrying in {:?}",
+            tasks.len(),
+            delay
+        );
+        let execute_after = chrono::Utc::now() + chrono::Duration::from_std(delay)?;
+        for task in tasks {
+            reschedule_task(pool, task.id, task.n_attempts, execute_after)
+                .await
+                .context("Failed to reschedule task")?;
+        }
+
+        return Ok(());
+    }
+
+    if let SendError::AuthFailure(_) = e {
+        tracing::error!("The email provider rejected the credentials: {}", e);
+    }
+
+    for task in tasks {
+        let n_attempts = task.n_attempts + 1;
+
+        if n_attempts >= settings.max_attempts as i32 {
+            tracing::error!(
+                "Giving up on delivering an email after {} attempts: {}",
+                n_attempts,
+                e
+            );
+            delete_tasks(pool, &[task.id])
+                .await
+                .context("Failed to delete task")?;
+        } else {
+            let delay =
+                backoff_delay(n_attempts as u32, settings).max(e.retry_after().unwrap_or_default());
+            tracing::warn!("Failed to deliver an email, retrying in {:?}: {}", delay, e);
+            reschedule_task(
+                pool,
+                task.id,
+                n_attempts,
+                chrono::Utc::now() + chrono::Duration::from_std(delay)?,
+            )
+            .await
+            .context("Failed to reschedule task")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The delay before the next attempt, doubling with every failed one.
+fn backoff_delay(n_failed_attempts: u32, settings: &EmailDeliverySettings) -> Duration {
+    let exponent = n_failed_attempts.saturating_sub(1).min(31);
+    let delay_millis = settings
+        .base_backoff_millis
+        .saturating_mul(2u64.pow(exponent));
+
+    Duration::from_millis(delay_millis.min(settings.max_backoff_millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> EmailDeliverySettings {
+        EmailDeliverySettings {
+            max_attempts: 5,
+            base_backoff_millis: 1000,
+            max_backoff_millis: 5000,
+            poll_interval_millis: 1000,
+            lease_secs: 300,
+        }
+    }
+
+    #[test]
+    fn the_first_retry_waits_for_the_base_backoff() {
+        assert_eq!(Duration::from_secs(1), backoff_delay(1, &settings()));
+    }
+
+    #[test]
+    fn the_backoff_doubles_with_every_failed_attempt() {
+        assert_eq!(Duration::from_secs(2), backoff_delay(2, &settings()));
+        assert_eq!(Duration::from_secs(4), backoff_delay(3, &settings()));
+    }
+
+    #[test]
+    fn the_backoff_is_capped() {
+        assert_eq!(Duration::from_secs(5), backoff_delay(4, &settings()));
+        assert_eq!(Duration::from_secs(5), backoff_delay(u32::MAX, &settings()));
+    }
+}
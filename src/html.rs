@@ -0,0 +1,23 @@
+/// Escapes the characters that are special in HTML text and attribute values.
+///
+/// Unlike Tera's default, leaves `/` alone so that links stay readable.
+pub fn escape_html(str: &str) -> String {
+    str.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#x27;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::html::escape_html;
+
+    #[test]
+    fn special_characters_are_escaped() {
+        assert_eq!(
+            "&lt;a href=&quot;https://example.com&quot;&gt;Tom &amp; Jerry&#x27;s&lt;/a&gt;",
+            escape_html(r#"<a href="https://example.com">Tom & Jerry's</a>"#)
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use std::future::{ready, Ready};
+
+use actix_web::dev::Payload;
+use actix_web::error::ErrorBadRequest;
+use actix_web::{FromRequest, HttpRequest};
+
+pub static IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+#[derive(Debug)]
+pub struct IdempotencyKey(String);
+
+impl IdempotencyKey {
+    const MAX_LENGTH: usize = 50;
+
+    pub fn parse(raw_key: String) -> Result<Self, String> {
+        if raw_key.is_empty() {
+            return Err("idempotency key cannot be empty".into());
+        }
+
+        if raw_key.len() > Self::MAX_LENGTH {
+            return Err(format!(
+                "idempotency key cannot be longer than {} characters",
+                Self::MAX_LENGTH
+            ));
+        }
+
+        Ok(Self(raw_key))
+    }
+}
+
+impl AsRef<str> for IdempotencyKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromRequest for IdempotencyKey {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let key = req
+            .headers()
+            .get(IDEMPOTENCY_KEY_HEADER)
+            .ok_or_else(|| format!("missing the {IDEMPOTENCY_KEY_HEADER} header"))
+            .and_then(|v| {
+                v.to_str()
+                    .map_err(|_| format!("the {IDEMPOTENCY_KEY_HEADER} header is not valid ASCII"))
+            })
+            .and_then(|v| IdempotencyKey::parse(v.to_string()));
+
+        ready(key.map_err(ErrorBadRequest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::*;
+
+    #[test]
+    fn an_empty_key_is_rejected() {
+        assert_err!(IdempotencyKey::parse("".into()));
+    }
+
+    #[test]
+    fn a_50_characters_long_key_is_valid() {
+        assert_ok!(IdempotencyKey::parse("a".repeat(50)));
+    }
+
+    #[test]
+    fn a_key_longer_than_50_characters_is_rejected() {
+        assert_err!(IdempotencyKey::parse("a".repeat(51)));
+    }
+}
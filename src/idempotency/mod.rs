@@ -0,0 +1,5 @@
+pub use key::*;
+pub use persistence::*;
+
+mod key;
+mod persistence;
@@ -0,0 +1,177 @@
+use actix_web::body::to_bytes;
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+use sqlx::postgres::{PgHasArrayType, PgTypeInfo};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::idempotency::IdempotencyKey;
+
+#[derive(Debug, sqlx::Type)]
+#[sqlx(type_name = "header_pair")]
+struct HeaderPairRecord {
+    name: String,
+    value: Vec<u8>,
+}
+
+impl PgHasArrayType for HeaderPairRecord {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("_header_pair")
+    }
+}
+
+pub enum NextAction {
+    /// The request is the first one with this key: process it inside the transaction, which
+    /// holds the lock on the key until the response is saved with [`save_response`].
+    StartProcessing(Box<Transaction<'static, Postgres>>),
+    ReturnSavedResponse(HttpResponse),
+    /// The key was used by a request with another payload, which is not a retry
+    RejectReusedKey,
+}
+
+/// Claims the idempotency key for the user, remembering a hash of the request's payload.
+///
+/// A concurrent request with the same key blocks on the row lock until the first one commits
+/// its response, and then replays it if it has the same payload.
+#[tracing::instrument(name = "Try processing idempotent request", skip_all)]
+pub async fn try_processing(
+    pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+    request: &[u8],
+) -> Result<NextAction, anyhow::Error> {
+    let request_hash = Sha256::digest(request).to_vec();
+    let mut transaction = pool.begin().await?;
+
+    let n_inserted_rows = sqlx::query!(
+        r#"
+        INSERT INTO idempotency (user_id, idempotency_key, request_hash, created_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        idempotency_key.as_ref(),
+        request_hash
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to insert idempotency key")?
+    .rows_affected();
+
+    if n_inserted_rows > 0 {
+        return Ok(NextAction::StartProcessing(Box::new(transaction)));
+    }
+
+    let saved_response = get_saved_response(pool, idempotency_key, user_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Expected a saved response, but none was found"))?;
+
+    // The rows saved before the hashes were are assumed to be retries
+    if saved_response
+        .request_hash
+        .is_some_and(|hash| hash != request_hash)
+    {
+        return Ok(NextAction::RejectReusedKey);
+    }
+
+    Ok(NextAction::ReturnSavedResponse(saved_response.response))
+}
+
+struct SavedResponse {
+    /// Absent for the rows saved before the hashes were
+    request_hash: Option<Vec<u8>>,
+    response: HttpResponse,
+}
+
+#[tracing::instrument(name = "Get saved response", skip_all)]
+async fn get_saved_response(
+    pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<Option<SavedResponse>, anyhow::Error> {
+    let saved_response = sqlx::query!(
+        r#"
+        SELECT
+            request_hash,
+            response_status_code as "response_status_code!",
+            response_headers as "response_headers!: Vec<HeaderPairRecord>",
+            response_body as "response_body!"
+        FROM idempotency
+        WHERE user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch saved response")?;
+
+    let Some(r) = saved_response else {
+        return Ok(None);
+    };
+
+    let status_code = StatusCode::from_u16(r.response_status_code.try_into()?)?;
+    let mut response = HttpResponse::build(status_code);
+    for HeaderPairRecord { name, value } in r.response_headers {
+        response.append_header((name, value));
+    }
+
+    Ok(Some(SavedResponse {
+        request_hash: r.request_hash,
+        response: response.body(r.response_body),
+    }))
+}
+
+/// Stores the response of the first request, releasing the lock on the key, and hands the
+/// response back to be returned.
+#[tracing::instrument(name = "Save response", skip_all)]
+pub async fn save_response(
+    mut transaction: Transaction<'static, Postgres>,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+    http_response: HttpResponse,
+) -> Result<HttpResponse, anyhow::Error> {
+    let (response_head, body) = http_response.into_parts();
+    // `MessageBody::Error` is not `Send` + `Sync`, so it cannot be wrapped by `anyhow`
+    let body = to_bytes(body)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to read response body: {}", e))?;
+
+    let status_code = response_head.status().as_u16() as i16;
+    let headers: Vec<_> = response_head
+        .headers()
+        .iter()
+        .map(|(name, value)| HeaderPairRecord {
+            name: name.as_str().to_owned(),
+            value: value.as_bytes().to_owned(),
+        })
+        .collect();
+
+    sqlx::query_unchecked!(
+        r#"
+        UPDATE idempotency
+        SET
+            response_status_code = $3,
+            response_headers = $4,
+            response_body = $5
+        WHERE user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref(),
+        status_code,
+        headers,
+        body.as_ref()
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to save response")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit idempotency transaction")?;
+
+    Ok(response_head.set_body(body).map_into_boxed_body())
+}
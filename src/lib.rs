@@ -1,6 +1,14 @@
+pub mod authentication;
+pub mod cleanup_worker;
 pub mod domain;
 pub mod email;
+pub mod email_delivery_queue;
+pub mod email_delivery_worker;
+pub mod html;
+pub mod idempotency;
+pub mod metrics;
 pub mod routes;
+pub mod session_store;
 pub mod settings;
 pub mod startup;
 pub mod telemetry;
@@ -1,31 +1,111 @@
+use std::fmt::{Debug, Display};
 use std::net::TcpListener;
 use std::time::Duration;
 
 use sqlx::postgres::PgPoolOptions;
+use tokio::task::JoinError;
 
-use zero2prod::email::EmailClient;
-use zero2prod::settings::SETTINGS;
-use zero2prod::startup::run_server;
+use zero2prod::cleanup_worker::run_cleanup_until_stopped;
+use zero2prod::email::{build_email_sender, EmailTemplates};
+use zero2prod::email_delivery_worker::run_worker_until_stopped;
+use zero2prod::routes::ReadinessChecks;
+use zero2prod::settings::{EmailProvider, SETTINGS};
+use zero2prod::startup::{run_metrics_server, run_server, ServerSettings};
 use zero2prod::telemetry::{build_subscriber, register_global_subscriber};
 
 #[tokio::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> Result<(), anyhow::Error> {
     let subscriber = build_subscriber("zero2prod".into(), "info");
     register_global_subscriber(subscriber);
 
     let pool = PgPoolOptions::new()
+        .max_connections(SETTINGS.database.max_connections)
         .acquire_timeout(Duration::from_secs(2))
         .connect_with(SETTINGS.database.with_db())
         .await
         .expect("Failed to connect to Postgres");
 
-    let listener = {
-        let (host, port) = (&SETTINGS.app.host, SETTINGS.app.port);
-        let address = format!("{host}:{port}");
+    let email_templates =
+        EmailTemplates::load(&SETTINGS.email_templates).expect("Failed to load email templates");
+
+    let bind = |port| {
+        let address = format!("{}:{port}", SETTINGS.app.host);
         TcpListener::bind(address).unwrap_or_else(|_| panic!("Failed to bind to port {port}"))
     };
+    let listener = bind(SETTINGS.app.port);
+    let metrics_listener = bind(SETTINGS.app.metrics_port);
+
+    let server = run_server(
+        listener,
+        &pool,
+        ServerSettings {
+            app_base_url: SETTINGS.app.base_url.clone(),
+            cookie_signing_key: SETTINGS.app.cookie_signing_key()?,
+            session_expiry: Duration::from_secs(SETTINGS.app.session_expiry_secs),
+            hmac_secret: SETTINGS.app.hmac_secret()?,
+            subscription_token_expiry: Duration::from_secs(
+                SETTINGS.app.subscription_token_expiry_secs,
+            ),
+            email_templates,
+            readiness_checks: ReadinessChecks {
+                timeout: Duration::from_millis(SETTINGS.readiness.timeout_millis),
+                max_connections: SETTINGS.database.max_connections,
+                email_provider: SETTINGS
+                    .readiness
+                    .check_email_provider
+                    .then(|| SETTINGS.email_client.provider_address())
+                    .flatten(),
+            },
+            // Emails are captured instead of sent in development
+            enable_dev_mailbox: SETTINGS.email_client.provider == EmailProvider::Capture,
+        },
+    )?;
+
+    let metrics_server = run_metrics_server(metrics_listener, &pool)?;
+
+    let worker = run_worker_until_stopped(
+        pool.clone(),
+        build_email_sender(&SETTINGS.email_client, &pool),
+        SETTINGS.email_delivery.clone(),
+    );
+
+    let cleanup = run_cleanup_until_stopped(pool.clone(), SETTINGS.cleanup.clone());
 
-    let email_client = EmailClient::default();
+    let server_task = tokio::spawn(server);
+    let metrics_server_task = tokio::spawn(metrics_server);
+    let worker_task = tokio::spawn(worker);
+    let cleanup_task = tokio::spawn(cleanup);
+
+    tokio::select! {
+        o = server_task => report_exit("API", o),
+        o = metrics_server_task => report_exit("Metrics server", o),
+        o = worker_task => report_exit("Email delivery worker", o),
+        o = cleanup_task => report_exit("Cleanup worker", o),
+    };
+
+    Ok(())
+}
 
-    run_server(listener, &pool, email_client, SETTINGS.app.base_url.clone())?.await
+fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>, JoinError>) {
+    match outcome {
+        Ok(Ok(())) => {
+            tracing::info!("{} has exited", task_name)
+        }
+        Ok(Err(e)) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "{} failed",
+                task_name
+            )
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "{} task failed to complete",
+                task_name
+            )
+        }
+    }
 }
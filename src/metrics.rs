@@ -0,0 +1,143 @@
+use std::future::Future;
+use std::time::Instant;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::{web, HttpResponse, Responder};
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram_vec_with_registry, register_int_counter_vec_with_registry,
+    register_int_counter_with_registry, register_int_gauge_with_registry, Encoder, HistogramVec,
+    IntCounter, IntCounterVec, IntGauge, Registry, TextEncoder,
+};
+use sqlx::PgPool;
+
+/// The metrics of the process, exposed at `/metrics` in the Prometheus text format.
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_request_duration: HistogramVec,
+    pub db_pool_connections: IntGauge,
+    pub db_pool_idle_connections: IntGauge,
+    pub email_send_attempts: IntCounterVec,
+    pub email_send_failures: IntCounterVec,
+    pub subscriptions_created: IntCounter,
+    pub subscriptions_confirmed: IntCounter,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("zero2prod".into()), None)
+            .expect("The metrics prefix should be valid");
+
+        Self {
+            http_requests: register_int_counter_vec_with_registry!(
+                "http_requests_total",
+                "HTTP requests by route, method and status",
+                &["route", "method", "status"],
+                registry
+            )
+            .unwrap(),
+            http_request_duration: register_histogram_vec_with_registry!(
+                "http_request_duration_seconds",
+                "Latency of HTTP requests by route and method",
+                &["route", "method"],
+                registry
+            )
+            .unwrap(),
+            db_pool_connections: register_int_gauge_with_registry!(
+                "db_pool_connections",
+                "Open connections of the database pool",
+                registry
+            )
+            .unwrap(),
+            db_pool_idle_connections: register_int_gauge_with_registry!(
+                "db_pool_idle_connections",
+                "Idle connections of the database pool",
+                registry
+            )
+            .unwrap(),
+            email_send_attempts: register_int_counter_vec_with_registry!(
+                "email_send_attempts_total",
+                "Attempts to send an email or a batch of emails by provider",
+                &["provider"],
+                registry
+            )
+            .unwrap(),
+            email_send_failures: register_int_counter_vec_with_registry!(
+                "email_send_failures_total",
+                "Failed attempts to send an email or a batch of emails by provider and kind",
+                &["provider", "kind"],
+                registry
+            )
+            .unwrap(),
+            subscriptions_created: register_int_counter_with_registry!(
+                "subscriptions_created_total",
+                "New subscribers pending confirmation",
+                registry
+            )
+            .unwrap(),
+            subscriptions_confirmed: register_int_counter_with_registry!(
+                "subscriptions_confirmed_total",
+                "Subscriptions confirmed by the subscribers",
+                registry
+            )
+            .unwrap(),
+            registry,
+        }
+    }
+}
+
+/// Serves the metrics, sampling the database pool on every scrape.
+pub async fn metrics(pool: web::Data<PgPool>) -> impl Responder {
+    METRICS.db_pool_connections.set(pool.size().into());
+    METRICS
+        .db_pool_idle_connections
+        .set(pool.num_idle().try_into().unwrap_or(i64::MAX));
+
+    let encoder = TextEncoder::new();
+    let mut body = vec![];
+    if let Err(e) = encoder.encode(&METRICS.registry.gather(), &mut body) {
+        tracing::error!("Failed to encode the metrics: {}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    HttpResponse::Ok()
+        .content_type(encoder.format_type())
+        .body(body)
+}
+
+/// Counts and times the requests by the route they matched, for `App::wrap_fn`.
+pub fn record_http_metrics<S, B>(
+    request: ServiceRequest,
+    service: &S,
+) -> impl Future<Output = Result<ServiceResponse<B>, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    // Unmatched paths share a label, so that scanners do not blow up the number of series
+    let route = request
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".into());
+    let method = request.method().to_string();
+    let start = Instant::now();
+    let response = service.call(request);
+
+    async move {
+        let response = response.await?;
+
+        METRICS
+            .http_request_duration
+            .with_label_values(&[&route, &method])
+            .observe(start.elapsed().as_secs_f64());
+        METRICS
+            .http_requests
+            .with_label_values(&[&route, &method, response.status().as_str()])
+            .inc();
+
+        Ok(response)
+    }
+}
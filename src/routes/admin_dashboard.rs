@@ -0,0 +1,62 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse, Responder};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::TypedSession;
+use crate::html::escape_html;
+use crate::routes::see_other;
+use crate::startup::{ADMIN_LOGOUT_PATH, LOGIN_PATH};
+
+#[tracing::instrument(name = "Admin dashboard", skip_all)]
+pub async fn admin_dashboard(session: TypedSession, pool: web::Data<PgPool>) -> impl Responder {
+    let internal_server_error = || HttpResponse::InternalServerError().finish();
+
+    let user_id = match session.get_user_id() {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => return see_other(LOGIN_PATH),
+        Err(_) => return internal_server_error(),
+    };
+
+    let username = match get_username(&user_id, &pool).await {
+        Ok(Some(username)) => username,
+        // The user was deleted since logging in
+        Ok(None) => {
+            session.log_out();
+            return see_other(LOGIN_PATH);
+        }
+        Err(_) => return internal_server_error(),
+    };
+
+    let username = escape_html(&username);
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Admin dashboard</title>
+</head>
+<body>
+    <p>Welcome {username}!</p>
+    <form name="logoutForm" action="/{ADMIN_LOGOUT_PATH}" method="post">
+        <input type="submit" value="Logout">
+    </form>
+</body>
+</html>"#,
+        ))
+}
+
+#[tracing::instrument(name = "Get username", skip_all)]
+async fn get_username(user_id: &Uuid, pool: &PgPool) -> Result<Option<String>, sqlx::Error> {
+    let user = sqlx::query!("SELECT username FROM users WHERE id = $1", user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to fetch username: {}", e);
+            e
+        })?;
+
+    Ok(user.map(|u| u.username))
+}
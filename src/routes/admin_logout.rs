@@ -0,0 +1,20 @@
+use actix_web::{HttpResponse, Responder};
+use actix_web_flash_messages::FlashMessage;
+
+use crate::authentication::TypedSession;
+use crate::routes::see_other;
+use crate::startup::LOGIN_PATH;
+
+#[tracing::instrument(name = "Logging out", skip_all)]
+pub async fn log_out(session: TypedSession) -> impl Responder {
+    match session.get_user_id() {
+        Ok(Some(_)) => {}
+        Ok(None) => return see_other(LOGIN_PATH),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    session.log_out();
+    FlashMessage::info("You have successfully logged out.").send();
+
+    see_other(LOGIN_PATH)
+}
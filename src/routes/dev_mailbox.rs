@@ -0,0 +1,134 @@
+use std::fmt::Write;
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse, Responder};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::html::escape_html;
+use crate::startup::DEV_MAILBOX_PATH;
+
+#[derive(serde::Deserialize)]
+pub struct MailboxParameters {
+    /// Only lists the emails sent to this recipient when present
+    to: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct CapturedEmail {
+    id: Uuid,
+    recipient: String,
+    subject: String,
+    html_content: String,
+    text_content: String,
+    unsubscribe_url: Option<String>,
+    created_at: String,
+}
+
+#[tracing::instrument(name = "Development mailbox", skip_all)]
+pub async fn dev_mailbox(pool: web::Data<PgPool>) -> impl Responder {
+    let emails = match get_captured_emails(None, &pool).await {
+        Ok(emails) => emails,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let mut rows_html = String::new();
+    for email in emails {
+        writeln!(
+            rows_html,
+            r#"<tr><td>{}</td><td>{}</td><td><a href="/{DEV_MAILBOX_PATH}/{}">{}</a></td></tr>"#,
+            email.created_at,
+            escape_html(&email.recipient),
+            email.id,
+            escape_html(&email.subject)
+        )
+        .unwrap();
+    }
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Mailbox</title>
+</head>
+<body>
+    <table>
+        <tr><th>Sent at</th><th>To</th><th>Subject</th></tr>
+        {rows_html}
+    </table>
+</body>
+</html>"#,
+        ))
+}
+
+#[tracing::instrument(name = "Development mailbox emails", skip_all)]
+pub async fn dev_mailbox_emails(
+    params: web::Query<MailboxParameters>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    match get_captured_emails(params.to.as_deref(), &pool).await {
+        Ok(emails) => HttpResponse::Ok().json(emails),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// Renders the HTML part of the email as it was sent.
+#[tracing::instrument(name = "Development mailbox email", skip_all)]
+pub async fn dev_mailbox_email(id: web::Path<Uuid>, pool: web::Data<PgPool>) -> impl Responder {
+    let email = sqlx::query!(
+        "SELECT html_content FROM captured_emails WHERE id = $1",
+        id.into_inner()
+    )
+    .fetch_optional(pool.get_ref())
+    .await;
+
+    match email {
+        Ok(Some(email)) => HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(email.html_content),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            tracing::error!("Failed to fetch captured email: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Get captured emails", skip_all)]
+async fn get_captured_emails(
+    to: Option<&str>,
+    pool: &PgPool,
+) -> Result<Vec<CapturedEmail>, sqlx::Error> {
+    let emails = sqlx::query!(
+        r#"
+        SELECT
+            id, recipient, subject, html_content, text_content, unsubscribe_url, created_at
+        FROM captured_emails
+        WHERE $1::text IS NULL OR recipient = $1
+        ORDER BY created_at DESC
+        "#,
+        to
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to fetch captured emails: {}", e);
+        e
+    })?;
+
+    Ok(emails
+        .into_iter()
+        .map(|e| CapturedEmail {
+            id: e.id,
+            recipient: e.recipient,
+            subject: e.subject,
+            html_content: e.html_content,
+            text_content: e.text_content,
+            unsubscribe_url: e.unsubscribe_url,
+            created_at: e.created_at.to_rfc3339(),
+        })
+        .collect())
+}
@@ -0,0 +1,98 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use serde::de::DeserializeOwned;
+
+use crate::domain::FieldError;
+use crate::routes::ProblemDetails;
+
+static FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+static JSON_CONTENT_TYPE: &str = "application/json";
+
+/// A request body deserialized as a URL-encoded form or as JSON, depending on its `Content-Type`.
+pub struct FormOrJson<T>(pub T);
+
+#[derive(thiserror::Error, Debug)]
+pub enum BodyError {
+    #[error("The content type must be {FORM_CONTENT_TYPE} or {JSON_CONTENT_TYPE}")]
+    UnsupportedMediaType,
+    #[error("Failed to parse the body: {message}")]
+    Invalid { status: StatusCode, message: String },
+    #[error("The body has fields of the wrong type")]
+    InvalidFields(Vec<FieldError>),
+}
+
+impl BodyError {
+    fn invalid(e: actix_web::Error) -> Self {
+        Self::Invalid {
+            status: e.as_response_error().status_code(),
+            message: e.to_string(),
+        }
+    }
+
+    /// Reports the field that could not be deserialized, if the error is about one.
+    fn invalid_json(e: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        let field = e.path().to_string();
+        let e = e.into_inner();
+
+        // The path of the body itself, e.g. when a field is missing
+        if field == "." {
+            return Self::Invalid {
+                status: StatusCode::BAD_REQUEST,
+                message: e.to_string(),
+            };
+        }
+
+        Self::InvalidFields(vec![FieldError::new(field, "invalid_type", e)])
+    }
+}
+
+impl ResponseError for BodyError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::Invalid { status, .. } => *status,
+            Self::InvalidFields(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            Self::InvalidFields(errors) => ProblemDetails::validation(errors.clone()),
+            _ => ProblemDetails::from_status(self.status_code()).detail(self.to_string()),
+        }
+        .into_response()
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for FormOrJson<T> {
+    type Error = BodyError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        match req.content_type() {
+            content_type if content_type == FORM_CONTENT_TYPE => {
+                let form = web::Form::<T>::from_request(req, payload);
+                Box::pin(async move {
+                    form.await
+                        .map(|form| Self(form.into_inner()))
+                        .map_err(BodyError::invalid)
+                })
+            }
+            content_type if content_type == JSON_CONTENT_TYPE => {
+                // Deserialized from a value to know the path of the fields of the wrong type
+                let json = web::Json::<serde_json::Value>::from_request(req, payload);
+                Box::pin(async move {
+                    let json = json.await.map_err(BodyError::invalid)?;
+                    serde_path_to_error::deserialize(json.into_inner())
+                        .map(Self)
+                        .map_err(BodyError::invalid_json)
+                })
+            }
+            _ => Box::pin(async { Err(BodyError::UnsupportedMediaType) }),
+        }
+    }
+}
@@ -1,5 +1,160 @@
-use actix_web::{HttpResponse, Responder};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
 
+use actix_web::{web, HttpResponse, Responder};
+use sqlx::{Connection, PgConnection, PgPool};
+
+/// Liveness, which does not depend on anything else.
 pub async fn health_check() -> impl Responder {
     HttpResponse::Ok()
 }
+
+/// What `/ready` checks besides the database.
+#[derive(Clone)]
+pub struct ReadinessChecks {
+    /// How long each check may take
+    pub timeout: Duration,
+    /// The maximum size of the database pool
+    pub max_connections: u32,
+    /// The host and port of the email provider, if its reachability is reported
+    pub email_provider: Option<(String, u16)>,
+}
+
+/// What `/ready` responds with, leaving the details of the failures to the logs.
+#[derive(serde::Serialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub checks: BTreeMap<&'static str, DependencyCheck>,
+}
+
+#[derive(serde::Serialize)]
+pub struct DependencyCheck {
+    pub up: bool,
+    /// Whether the instance is not ready while the dependency is down
+    pub required: bool,
+    /// Not set for the states that are read rather than checked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_millis: Option<u128>,
+}
+
+/// The connection that checks the database while the pool is saturated, so that probes never open
+/// more than one connection besides the pool.
+#[derive(Default)]
+pub struct SpareDatabaseConnection(tokio::sync::Mutex<Option<PgConnection>>);
+
+struct PoolStats {
+    size: u32,
+    idle: usize,
+    max_size: u32,
+    /// Every connection is open and in use, so requests wait for one
+    saturated: bool,
+}
+
+/// Readiness, which responds with 503 while a required dependency is down.
+pub async fn readiness_check(
+    pool: web::Data<PgPool>,
+    checks: web::Data<ReadinessChecks>,
+    spare_connection: web::Data<SpareDatabaseConnection>,
+) -> impl Responder {
+    let mut report = ReadinessReport {
+        ready: true,
+        checks: BTreeMap::new(),
+    };
+
+    // Taken before the check acquires a connection
+    let pool_stats = pool_stats(&pool, checks.max_connections);
+
+    let database = check("database", checks.timeout, true, async {
+        if pool_stats.saturated {
+            // Waiting for a connection of the pool would fail the check under load, although
+            // the database is up
+            let mut spare_connection = spare_connection.0.lock().await;
+            let mut connection = match spare_connection.take() {
+                Some(connection) => connection,
+                None => PgConnection::connect_with(pool.connect_options()).await?,
+            };
+            connection.ping().await?;
+            *spare_connection = Some(connection);
+            Ok(())
+        } else {
+            pool.acquire().await?.ping().await
+        }
+    })
+    .await;
+    report.checks.insert("database", database);
+
+    // Requests wait for connections, but the instance can still serve them
+    if pool_stats.saturated {
+        tracing::warn!(
+            size = pool_stats.size,
+            idle = pool_stats.idle,
+            max_size = pool_stats.max_size,
+            "The database pool is saturated"
+        );
+    }
+    report.checks.insert(
+        "database_pool",
+        DependencyCheck {
+            up: !pool_stats.saturated,
+            required: false,
+            duration_millis: None,
+        },
+    );
+
+    if let Some((host, port)) = &checks.email_provider {
+        // Emails are queued until the provider is reachable again
+        let email_provider = check("email_provider", checks.timeout, false, async {
+            tokio::net::TcpStream::connect((host.as_str(), *port))
+                .await
+                .map(|_| ())
+        })
+        .await;
+        report.checks.insert("email_provider", email_provider);
+    }
+
+    report.ready = report.checks.values().all(|c| c.up || !c.required);
+    if report.ready {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+/// Runs a check, logging why it failed.
+async fn check<E: std::fmt::Display>(
+    name: &str,
+    timeout: Duration,
+    required: bool,
+    check: impl Future<Output = Result<(), E>>,
+) -> DependencyCheck {
+    let start = Instant::now();
+
+    let error = match tokio::time::timeout(timeout, check).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some(format!("Timed out after {timeout:?}")),
+    };
+
+    if let Some(e) = &error {
+        tracing::warn!(required, "The {} check failed: {}", name, e);
+    }
+
+    DependencyCheck {
+        up: error.is_none(),
+        required,
+        duration_millis: Some(start.elapsed().as_millis()),
+    }
+}
+
+fn pool_stats(pool: &PgPool, max_size: u32) -> PoolStats {
+    let size = pool.size();
+    let idle = pool.num_idle();
+
+    PoolStats {
+        size,
+        idle,
+        max_size,
+        saturated: size >= max_size && idle == 0,
+    }
+}
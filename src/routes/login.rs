@@ -0,0 +1,91 @@
+use std::fmt::Write;
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse, Responder};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use secrecy::Secret;
+use sqlx::PgPool;
+
+use crate::authentication::{validate_credentials, AuthError, Credentials, TypedSession};
+use crate::html::escape_html;
+use crate::routes::see_other;
+use crate::startup::{ADMIN_DASHBOARD_PATH, LOGIN_PATH};
+
+#[derive(serde::Deserialize)]
+pub struct LoginData {
+    username: String,
+    password: Secret<String>,
+}
+
+pub async fn login_form(flash_messages: IncomingFlashMessages) -> impl Responder {
+    let mut messages_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(
+            messages_html,
+            "<p><i>{}</i></p>",
+            escape_html(message.content())
+        )
+        .unwrap();
+    }
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Login</title>
+</head>
+<body>
+    {messages_html}
+    <form action="/{LOGIN_PATH}" method="post">
+        <label>Username
+            <input type="text" placeholder="Enter Username" name="username">
+        </label>
+        <label>Password
+            <input type="password" placeholder="Enter Password" name="password">
+        </label>
+        <button type="submit">Login</button>
+    </form>
+</body>
+</html>"#,
+        ))
+}
+
+#[tracing::instrument(
+    name = "Logging in",
+    skip_all,
+    fields(username = %form.username, user_id),
+)]
+pub async fn login(
+    form: web::Form<LoginData>,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+) -> impl Responder {
+    let credentials = Credentials {
+        username: form.0.username,
+        password: form.0.password,
+    };
+
+    let user_id = match validate_credentials(credentials, &pool).await {
+        Ok(user_id) => user_id,
+        Err(AuthError::InvalidCredentials(_)) => {
+            FlashMessage::error("Authentication failed.").send();
+            return see_other(LOGIN_PATH);
+        }
+        Err(AuthError::UnexpectedError(e)) => {
+            tracing::error!("Failed to validate credentials: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    session.renew();
+    if let Err(e) = session.insert_user_id(user_id) {
+        tracing::error!("Failed to insert user id into session: {}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    see_other(ADMIN_DASHBOARD_PATH)
+}
@@ -1,7 +1,25 @@
+pub use admin_dashboard::*;
+pub use admin_logout::*;
+pub use dev_mailbox::*;
+pub use form_or_json::*;
 pub use health_check::*;
+pub use login::*;
+pub use newsletters::*;
+pub use problem_details::*;
+pub use redirect::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_unsubscribe::*;
 
+mod admin_dashboard;
+mod admin_logout;
+mod dev_mailbox;
+mod form_or_json;
 mod health_check;
+mod login;
+mod newsletters;
+mod problem_details;
+mod redirect;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
@@ -0,0 +1,172 @@
+use actix_web::{web, HttpResponse, Responder};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::AuthenticatedUser;
+use crate::domain::{EmailAddress, SubscriptionStatus};
+use crate::email::{EmailTemplates, NewsletterEmail};
+use crate::email_delivery_queue::{
+    enqueue_newsletter_issue, insert_newsletter_issue, UNSUBSCRIBE_URL_KEY,
+};
+use crate::idempotency::{save_response, try_processing, IdempotencyKey, NextAction};
+use crate::routes::unsubscribe_url;
+use crate::settings::{AppBaseUrl, HmacSecret};
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct NewsletterData {
+    title: String,
+    content: NewsletterContent,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct NewsletterContent {
+    html: String,
+    text: String,
+}
+
+struct ConfirmedSubscriber {
+    id: Uuid,
+    email: EmailAddress,
+}
+
+#[tracing::instrument(
+    name = "Publishing newsletter",
+    skip_all,
+    fields(
+        user_id = %user.user_id,
+        idempotency_key = %idempotency_key.as_ref(),
+        newsletter.title = %newsletter.title,
+    ),
+)]
+pub async fn publish_newsletter(
+    user: AuthenticatedUser,
+    idempotency_key: IdempotencyKey,
+    newsletter: web::Json<NewsletterData>,
+    pool: web::Data<PgPool>,
+    app_base_url: web::Data<AppBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+    email_templates: web::Data<EmailTemplates>,
+) -> impl Responder {
+    let internal_server_error = || HttpResponse::InternalServerError().finish();
+
+    // Serialized again so that only the content tells retries apart, not the formatting
+    let request = match serde_json::to_vec(&newsletter.0) {
+        Ok(request) => request,
+        Err(e) => {
+            tracing::error!("Failed to serialize the newsletter: {:?}", e);
+            return internal_server_error();
+        }
+    };
+
+    let mut transaction =
+        match try_processing(&pool, &idempotency_key, user.user_id, &request).await {
+            // Dropping the transaction without saving a response rolls back the claim on the
+            // key, so that failed requests can be retried
+            Ok(NextAction::StartProcessing(t)) => *t,
+            Ok(NextAction::ReturnSavedResponse(saved_response)) => return saved_response,
+            Ok(NextAction::RejectReusedKey) => {
+                return HttpResponse::UnprocessableEntity()
+                    .body("The idempotency key was already used to publish another newsletter")
+            }
+            Err(e) => {
+                tracing::error!("Failed to process idempotency key: {:?}", e);
+                return internal_server_error();
+            }
+        };
+
+    let subscribers = match get_confirmed_subscribers(&pool).await {
+        Ok(s) => s,
+        Err(_) => return internal_server_error(),
+    };
+
+    // The language of subscribers is unknown, so newsletters are in the default locale. Each
+    // subscriber's unsubscribe URL is substituted when the issue is delivered
+    let email = match email_templates.render(
+        email_templates.default_locale(),
+        &NewsletterEmail {
+            title: newsletter.title.clone(),
+            html_content: newsletter.content.html.clone(),
+            text_content: newsletter.content.text.clone(),
+            unsubscribe_url: UNSUBSCRIBE_URL_KEY.into(),
+        },
+    ) {
+        Ok(email) => email,
+        Err(e) => {
+            tracing::error!("Failed to render the newsletter email: {:?}", e);
+            return internal_server_error();
+        }
+    };
+
+    let recipients = subscribers
+        .into_iter()
+        .filter_map(|subscriber| match subscriber {
+            Ok(s) => Some(s),
+            Err(e) => {
+                tracing::warn!(
+                    "Skipping a confirmed subscriber with an invalid email: {}",
+                    e
+                );
+                None
+            }
+        })
+        .map(|subscriber| {
+            let unsubscribe_url =
+                unsubscribe_url(&app_base_url.as_ref().0, &subscriber.id, &hmac_secret);
+            (subscriber.email, unsubscribe_url)
+        })
+        .collect::<Vec<_>>();
+
+    let newsletter_issue_id = match insert_newsletter_issue(
+        &mut transaction,
+        &email.subject,
+        &email.html_content,
+        email.text_content.as_deref(),
+    )
+    .await
+    {
+        Ok(id) => id,
+        Err(_) => return internal_server_error(),
+    };
+
+    if enqueue_newsletter_issue(&mut transaction, newsletter_issue_id, &recipients)
+        .await
+        .is_err()
+    {
+        return internal_server_error();
+    }
+
+    // The newsletter is delivered by the email delivery worker
+    let response = HttpResponse::Accepted().finish();
+    match save_response(transaction, &idempotency_key, user.user_id, response).await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::error!("Failed to save idempotent response: {:?}", e);
+            internal_server_error()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Get confirmed subscribers", skip_all)]
+async fn get_confirmed_subscribers(
+    pool: &PgPool,
+) -> Result<Vec<Result<ConfirmedSubscriber, String>>, sqlx::Error> {
+    let subscribers = sqlx::query!(
+        "SELECT id, email FROM subscriptions WHERE status = $1",
+        SubscriptionStatus::Confirmed as _
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to fetch confirmed subscribers: {}", e);
+        e
+    })?;
+
+    Ok(subscribers
+        .into_iter()
+        .map(|s| {
+            EmailAddress::parse(s.email.clone())
+                .map(|email| ConfirmedSubscriber { id: s.id, email })
+                .map_err(|e| format!("{}: {e}", s.email))
+        })
+        .collect())
+}
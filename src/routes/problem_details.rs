@@ -0,0 +1,63 @@
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+
+use crate::domain::FieldError;
+
+pub static VALIDATION_PROBLEM_TYPE: &str = "/problems/validation-error";
+
+/// An `application/problem+json` error response body (RFC 7807).
+#[derive(Debug, serde::Serialize)]
+pub struct ProblemDetails {
+    /// Identifies the problem, `about:blank` when the status says it all
+    #[serde(rename = "type")]
+    pub problem_type: &'static str,
+    pub title: &'static str,
+    #[serde(serialize_with = "serialize_status")]
+    pub status: StatusCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// The invalid fields of the request, if any
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl ProblemDetails {
+    /// A problem without more details than its status.
+    pub fn from_status(status: StatusCode) -> Self {
+        Self {
+            problem_type: "about:blank",
+            title: status.canonical_reason().unwrap_or_default(),
+            status,
+            detail: None,
+            errors: vec![],
+        }
+    }
+
+    pub fn validation(errors: Vec<FieldError>) -> Self {
+        Self {
+            problem_type: VALIDATION_PROBLEM_TYPE,
+            title: "The request has invalid fields",
+            status: StatusCode::BAD_REQUEST,
+            detail: None,
+            errors,
+        }
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn into_response(self) -> HttpResponse {
+        HttpResponse::build(self.status)
+            .content_type("application/problem+json")
+            .json(self)
+    }
+}
+
+fn serialize_status<S: serde::Serializer>(
+    status: &StatusCode,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u16(status.as_u16())
+}
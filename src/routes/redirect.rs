@@ -0,0 +1,9 @@
+use actix_web::http::header::LOCATION;
+use actix_web::HttpResponse;
+
+/// Redirects the browser with a `GET` to the given app path.
+pub fn see_other(path: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((LOCATION, format!("/{path}")))
+        .finish()
+}
@@ -1,13 +1,18 @@
-use actix_web::{web, HttpResponse, Responder};
-use rand::distributions;
-use rand::{thread_rng, Rng};
+use actix_web::http::header::AcceptLanguage;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-use crate::domain::{RawSubscriber, Subscriber, SubscriptionStatus};
-use crate::email::{EmailClient, EmailData};
-use crate::settings::AppBaseUrl;
+use crate::domain::{FieldError, RawSubscriber, Subscriber, SubscriptionStatus, SubscriptionToken};
+use crate::email::{ConfirmationEmail, EmailData, EmailTemplates};
+use crate::email_delivery_queue::enqueue_email;
+use crate::metrics::METRICS;
+use crate::routes::{unsubscribe_url, FormOrJson, ProblemDetails};
+use crate::settings::{AppBaseUrl, HmacSecret, SubscriptionTokenExpiry};
 use crate::startup::SUBSCRIPTIONS_CONFIRM_PATH;
+use crate::telemetry::error_chain_fmt;
 
 #[tracing::instrument(
     name = "Adding new subscriber",
@@ -18,139 +23,256 @@ use crate::startup::SUBSCRIPTIONS_CONFIRM_PATH;
     ),
 )]
 pub async fn subscribe(
-    subscriber: web::Form<RawSubscriber>,
+    request: HttpRequest,
+    subscriber: FormOrJson<RawSubscriber>,
     pool: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
     app_base_url: web::Data<AppBaseUrl>,
-) -> impl Responder {
-    let subscriber: Subscriber = match subscriber.0.try_into() {
-        Ok(s) => s,
-        Err(e) => return HttpResponse::BadRequest().json(SubscriptionResponse { message: e }),
-    };
-
-    let internal_server_error = || {
-        HttpResponse::InternalServerError().json(SubscriptionResponse {
-            message: "Failed to subscribe".into(),
-        })
-    };
-
-    let mut transaction = match pool.begin().await {
-        Ok(t) => t,
-        Err(_) => return internal_server_error(),
-    };
-
-    let subscriber_id = match insert_subscriber(&subscriber, &mut transaction).await {
-        Ok(id) => id,
-        Err(_) => return internal_server_error(),
-    };
+    hmac_secret: web::Data<HmacSecret>,
+    token_expiry: web::Data<SubscriptionTokenExpiry>,
+    email_templates: web::Data<EmailTemplates>,
+) -> Result<HttpResponse, SubscribeError> {
+    let subscriber: Subscriber = subscriber
+        .0
+        .try_into()
+        .map_err(SubscribeError::ValidationError)?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+
+    let StoredSubscriber {
+        id: subscriber_id,
+        status,
+        is_new,
+    } = upsert_subscriber(&subscriber, &mut transaction)
+        .await
+        .context("Failed to store the new subscriber")?;
+
+    // Subscribing again restarts the double opt-in unless the subscriber is already confirmed,
+    // and the response is the same either way so that it does not reveal who is subscribed
+    if status == SubscriptionStatus::Confirmed {
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit the transaction storing the new subscriber")?;
+
+        return Ok(HttpResponse::Created().json(SubscriptionResponse {
+            message: "Subscribed!".into(),
+        }));
+    }
+
+    if status == SubscriptionStatus::Unsubscribed {
+        mark_subscriber_pending_confirmation(&subscriber_id, &mut transaction)
+            .await
+            .context("Failed to mark the subscriber as pending confirmation")?;
+    }
 
     let subscription_token =
-        match insert_random_subscription_token(&subscriber_id, &mut transaction).await {
-            Ok(token) => token,
-            Err(_) => return internal_server_error(),
-        };
-
-    if transaction.commit().await.is_err()
-        || send_confirmation_email(
-            &email_client,
-            &subscriber,
-            &app_base_url.as_ref().0,
-            &subscription_token,
-        )
+        insert_random_subscription_token(&subscriber_id, &token_expiry, &mut transaction)
+            .await
+            .context("Failed to store the subscription token")?;
+
+    let email_data = confirmation_email(
+        &subscriber,
+        &subscriber_id,
+        &subscription_token,
+        &app_base_url.as_ref().0,
+        &hmac_secret,
+        &email_templates,
+        email_templates.negotiate_locale(preferred_languages(&request).iter().map(String::as_str)),
+    )
+    .context("Failed to render the confirmation email")?;
+
+    // The email is queued in the same transaction as the subscriber (transactional outbox), so
+    // either both are persisted or neither is, and the email delivery worker sends it afterwards
+    enqueue_email(&mut transaction, &email_data)
+        .await
+        .context("Failed to enqueue the confirmation email")?;
+    transaction
+        .commit()
         .await
-        .is_err()
-    {
-        return internal_server_error();
-    };
+        .context("Failed to commit the transaction storing the new subscriber")?;
 
-    HttpResponse::Created().json(SubscriptionResponse {
+    if is_new {
+        METRICS.subscriptions_created.inc();
+    }
+
+    Ok(HttpResponse::Created().json(SubscriptionResponse {
         message: "Subscribed!".into(),
-    })
+    }))
+}
+
+#[derive(thiserror::Error)]
+pub enum SubscribeError {
+    #[error(
+        "The subscriber is invalid: {}",
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
+    ValidationError(Vec<FieldError>),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for SubscribeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::ValidationError(_) => StatusCode::BAD_REQUEST,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            Self::ValidationError(errors) => ProblemDetails::validation(errors.clone()),
+            Self::UnexpectedError(_) => {
+                ProblemDetails::from_status(self.status_code()).detail("Failed to subscribe")
+            }
+        }
+        .into_response()
+    }
+}
+
+struct StoredSubscriber {
+    id: Uuid,
+    status: SubscriptionStatus,
+    /// Whether the subscriber was just inserted
+    is_new: bool,
 }
 
-#[tracing::instrument(name = "Inserting subscriber to DB", skip_all)]
-async fn insert_subscriber(
+/// Inserts the subscriber unless their email is already subscribed, and returns the stored
+/// subscriber, locked until the end of the transaction.
+#[tracing::instrument(name = "Upserting subscriber to DB", skip_all)]
+async fn upsert_subscriber(
     subscriber: &Subscriber,
     transaction: &mut Transaction<'_, Postgres>,
-) -> Result<Uuid, sqlx::Error> {
-    let subscriber_id = Uuid::new_v4();
-    sqlx::query!(
+) -> Result<StoredSubscriber, sqlx::Error> {
+    // Concurrent requests for the same email wait here for each other instead of failing on the
+    // unique constraint
+    let inserted = sqlx::query!(
         r#"
         INSERT INTO subscriptions
         VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (email) DO NOTHING
         "#,
-        subscriber_id,
+        Uuid::new_v4(),
         subscriber.email.as_ref(),
         subscriber.name.as_ref(),
         chrono::Utc::now(),
         SubscriptionStatus::PendingConfirmation as _
     )
+    .execute(&mut *transaction)
+    .await?;
+
+    let stored = sqlx::query!(
+        r#"
+        SELECT id, status as "status: SubscriptionStatus"
+        FROM subscriptions
+        WHERE email = $1
+        FOR UPDATE
+        "#,
+        subscriber.email.as_ref()
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+
+    Ok(StoredSubscriber {
+        id: stored.id,
+        status: stored.status,
+        is_new: inserted.rows_affected() == 1,
+    })
+}
+
+#[tracing::instrument(name = "Marking subscriber as pending confirmation", skip_all)]
+async fn mark_subscriber_pending_confirmation(
+    subscriber_id: &Uuid,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE subscriptions SET status = $1 WHERE id = $2",
+        SubscriptionStatus::PendingConfirmation as _,
+        subscriber_id
+    )
     .execute(transaction)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to insert subscriber: {}", e);
-        e
-    })?;
+    .await?;
 
-    Ok(subscriber_id)
+    Ok(())
 }
 
 #[tracing::instrument(name = "Inserting random subscription token to DB", skip_all)]
 async fn insert_random_subscription_token(
     subscription_id: &Uuid,
+    expiry: &SubscriptionTokenExpiry,
     transaction: &mut Transaction<'_, Postgres>,
-) -> Result<String, sqlx::Error> {
-    let token = gen_random_string(25);
+) -> Result<SubscriptionToken, sqlx::Error> {
+    let token = SubscriptionToken::generate();
+    let now = chrono::Utc::now();
+    let expires_at = now + expiry.0;
     sqlx::query!(
         r#"
-        INSERT INTO subscription_tokens
-        VALUES ($1, $2)
+        INSERT INTO subscription_tokens (token_hash, subscription_id, created_at, expires_at)
+        VALUES ($1, $2, $3, $4)
         "#,
-        token,
-        subscription_id
+        token.digest(),
+        subscription_id,
+        now,
+        expires_at
     )
     .execute(transaction)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to insert subscription token: {}", e);
-        e
-    })?;
+    .await?;
 
     Ok(token)
 }
 
-#[tracing::instrument(name = "Sending confirmation email", skip_all)]
-async fn send_confirmation_email(
-    email_client: &EmailClient,
+/// The languages of the `Accept-Language` header, most preferred first.
+fn preferred_languages(request: &HttpRequest) -> Vec<String> {
+    request
+        .get_header::<AcceptLanguage>()
+        .map(|header| header.ranked())
+        .unwrap_or_default()
+        .iter()
+        .map(|language| language.to_string())
+        .collect()
+}
+
+#[tracing::instrument(name = "Rendering confirmation email", skip_all)]
+fn confirmation_email(
     subscriber: &Subscriber,
+    subscriber_id: &Uuid,
+    token: &SubscriptionToken,
     base_url: &reqwest::Url,
-    token: &str,
-) -> Result<(), reqwest::Error> {
-    let confirmation_link = format!("{base_url}{SUBSCRIPTIONS_CONFIRM_PATH}?token={token}");
-
-    let email_data = EmailData {
-        to: subscriber.email.clone(),
-        subject: "Welcome!".into(),
-        content: format!(
-            r#"Welcome to my newsletter, {}!<br>
-<br>
-You may <a href="{confirmation_link}">confirm your subscription by clicking here!</a>
-"#,
-            subscriber.name.as_ref()
-        ),
-        content_type: "text/html".into(),
-    };
-
-    email_client.send(&email_data).await
-}
+    hmac_secret: &HmacSecret,
+    email_templates: &EmailTemplates,
+    locale: &str,
+) -> Result<EmailData, anyhow::Error> {
+    let confirmation_link = format!(
+        "{base_url}{SUBSCRIPTIONS_CONFIRM_PATH}?token={}",
+        token.as_ref()
+    );
+    let unsubscribe_url = unsubscribe_url(base_url, subscriber_id, hmac_secret);
 
-fn gen_random_string(len: usize) -> String {
-    let mut rng = thread_rng();
+    let email = email_templates.render(
+        locale,
+        &ConfirmationEmail {
+            subscriber_name: subscriber.name.as_ref().into(),
+            confirmation_link,
+            unsubscribe_url: unsubscribe_url.to_string(),
+        },
+    )?;
 
-    std::iter::repeat_with(|| rng.sample(distributions::Alphanumeric))
-        .map(char::from)
-        .take(len)
-        .collect()
+    Ok(EmailData {
+        to: subscriber.email.clone(),
+        subject: email.subject,
+        html_content: email.html_content,
+        text_content: email.text_content,
+        unsubscribe_url: Some(unsubscribe_url),
+    })
 }
 
 #[derive(serde::Serialize)]
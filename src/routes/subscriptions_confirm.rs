@@ -1,10 +1,14 @@
-use crate::domain::SubscriptionStatus;
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
+use crate::domain::{SubscriptionStatus, SubscriptionToken};
+use crate::metrics::METRICS;
+use crate::telemetry::error_chain_fmt;
+
 #[derive(serde::Deserialize)]
-#[allow(dead_code)]
 pub struct ConfirmSubscriptionParameters {
     token: String,
 }
@@ -13,60 +17,117 @@ pub struct ConfirmSubscriptionParameters {
 pub async fn confirm_subscription(
     params: web::Query<ConfirmSubscriptionParameters>,
     pool: web::Data<PgPool>,
-) -> impl Responder {
-    let internal_server_error = || HttpResponse::InternalServerError().finish();
-
-    let mut transaction = match pool.begin().await {
-        Ok(t) => t,
-        Err(_) => return internal_server_error(),
-    };
-
-    let subscription_token = &params.token;
-    let subscription_id =
-        match get_subscription_id_of_subscription_token(subscription_token, &mut transaction).await
-        {
-            Ok(v) => match v {
-                None => return HttpResponse::Unauthorized().finish(),
-                Some(v) => v,
-            },
-            Err(_) => return internal_server_error(),
-        };
-
-    if update_subscription_status(
-        &subscription_id,
+) -> Result<HttpResponse, ConfirmError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+
+    let subscription_token = SubscriptionToken::from(params.0.token);
+    let stored_token = get_subscription_token(&subscription_token, &mut transaction)
+        .await
+        .context("Failed to fetch the subscription token")?
+        .ok_or(ConfirmError::UnknownToken)?;
+
+    if stored_token.consumed_at.is_some() {
+        return Err(ConfirmError::ConsumedToken);
+    }
+    if stored_token.expires_at <= chrono::Utc::now() {
+        return Err(ConfirmError::ExpiredToken);
+    }
+
+    consume_subscription_token(&subscription_token, &mut transaction)
+        .await
+        .context("Failed to consume the subscription token")?;
+    update_subscription_status(
+        &stored_token.subscription_id,
         &SubscriptionStatus::Confirmed,
         &mut transaction,
     )
     .await
-    .is_err()
-    {
-        return internal_server_error();
+    .context("Failed to mark the subscriber as confirmed")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction confirming the subscriber")?;
+
+    METRICS.subscriptions_confirmed.inc();
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(thiserror::Error)]
+pub enum ConfirmError {
+    #[error("The subscription token is unknown")]
+    UnknownToken,
+    #[error("The subscription token was already used")]
+    ConsumedToken,
+    #[error("The subscription token expired")]
+    ExpiredToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ConfirmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
     }
+}
 
-    if transaction.commit().await.is_err() {
-        return internal_server_error();
+impl ResponseError for ConfirmError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnknownToken => StatusCode::UNAUTHORIZED,
+            Self::ConsumedToken => StatusCode::CONFLICT,
+            Self::ExpiredToken => StatusCode::GONE,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
     }
 
-    HttpResponse::Ok().finish()
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::new(self.status_code())
+    }
+}
+
+struct StoredSubscriptionToken {
+    subscription_id: Uuid,
+    expires_at: chrono::DateTime<chrono::Utc>,
+    consumed_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[tracing::instrument(name = "Get subscription id of subscription token", skip_all)]
-async fn get_subscription_id_of_subscription_token(
-    token: &str,
+/// The token is locked until the end of the transaction, so that it is consumed only once.
+#[tracing::instrument(name = "Get subscription token", skip_all)]
+async fn get_subscription_token(
+    token: &SubscriptionToken,
     transaction: &mut Transaction<'_, Postgres>,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let subscription_token = sqlx::query!(
-        "SELECT subscription_id FROM subscription_tokens WHERE id = $1",
-        token
+) -> Result<Option<StoredSubscriptionToken>, sqlx::Error> {
+    sqlx::query_as!(
+        StoredSubscriptionToken,
+        r#"
+        SELECT subscription_id, expires_at, consumed_at
+        FROM subscription_tokens
+        WHERE token_hash = $1
+        FOR UPDATE
+        "#,
+        token.digest()
     )
     .fetch_optional(transaction)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch subscription id: {}", e);
-        e
-    })?;
+}
+
+#[tracing::instrument(name = "Consume subscription token", skip_all)]
+async fn consume_subscription_token(
+    token: &SubscriptionToken,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE subscription_tokens SET consumed_at = now() WHERE token_hash = $1",
+        token.digest()
+    )
+    .execute(transaction)
+    .await?;
 
-    Ok(subscription_token.map(|v| v.subscription_id))
+    Ok(())
 }
 
 #[tracing::instrument(name = "Update subscription status", skip_all)]
@@ -81,11 +142,7 @@ async fn update_subscription_status(
         subscription_id
     )
     .execute(transaction)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to update subscription status: {}", e);
-        e
-    })?;
+    .await?;
 
     Ok(())
 }
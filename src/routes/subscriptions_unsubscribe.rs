@@ -0,0 +1,123 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse, Responder};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::domain::{SubscriptionStatus, UnsubscribeToken};
+use crate::settings::HmacSecret;
+use crate::startup::SUBSCRIPTIONS_UNSUBSCRIBE_PATH;
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeParameters {
+    token: String,
+}
+
+/// The link added to every email sent to the subscriber.
+pub fn unsubscribe_url(
+    base_url: &reqwest::Url,
+    subscriber_id: &Uuid,
+    hmac_secret: &HmacSecret,
+) -> reqwest::Url {
+    let token = UnsubscribeToken::sign(subscriber_id, &hmac_secret.0);
+
+    reqwest::Url::parse(&format!(
+        "{base_url}{SUBSCRIPTIONS_UNSUBSCRIBE_PATH}?token={}",
+        token.as_ref()
+    ))
+    .expect("The unsubscribe URL should be valid")
+}
+
+/// Asks for a confirmation, since links in emails may be followed by scanners and prefetchers.
+#[tracing::instrument(name = "Unsubscribe form", skip_all)]
+pub async fn unsubscribe_form(
+    params: web::Query<UnsubscribeParameters>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> impl Responder {
+    if UnsubscribeToken::verify(&params.token, &hmac_secret.0).is_err() {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Unsubscribe</title>
+</head>
+<body>
+    <form action="/{SUBSCRIPTIONS_UNSUBSCRIBE_PATH}?token={}" method="post">
+        <input type="hidden" name="List-Unsubscribe" value="One-Click">
+        <button type="submit">Unsubscribe</button>
+    </form>
+</body>
+</html>"#,
+            params.token
+        ))
+}
+
+/// Works without cookies, so that mail clients can unsubscribe with one click (RFC 8058).
+#[tracing::instrument(name = "Unsubscribe", skip_all, fields(subscriber_id))]
+pub async fn unsubscribe(
+    params: web::Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> impl Responder {
+    let internal_server_error = || HttpResponse::InternalServerError().finish();
+
+    let subscriber_id = match UnsubscribeToken::verify(&params.token, &hmac_secret.0) {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::Unauthorized().finish(),
+    };
+    tracing::Span::current().record("subscriber_id", tracing::field::display(&subscriber_id));
+
+    let mut transaction = match pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return internal_server_error(),
+    };
+
+    if unsubscribe_subscriber(&subscriber_id, &mut transaction)
+        .await
+        .is_err()
+        || transaction.commit().await.is_err()
+    {
+        return internal_server_error();
+    }
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body("<p>You have been unsubscribed.</p>")
+}
+
+#[tracing::instrument(name = "Unsubscribe subscriber", skip_all)]
+async fn unsubscribe_subscriber(
+    subscriber_id: &Uuid,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE subscriptions SET status = $1 WHERE id = $2",
+        SubscriptionStatus::Unsubscribed as _,
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to update subscription status: {}", e);
+        e
+    })?;
+
+    // Outstanding confirmation links must not subscribe them again
+    sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscription_id = $1",
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to delete subscription tokens: {}", e);
+        e
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use actix_web::cookie::time::Duration;
+use anyhow::Context;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sqlx::PgPool;
+
+type SessionState = HashMap<String, String>;
+
+/// Server-side session storage backed by the `sessions` table.
+///
+/// Only the session key ever leaves the server, in the (signed) session cookie.
+#[derive(Clone)]
+pub struct PgSessionStore {
+    pool: PgPool,
+}
+
+impl PgSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for PgSessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let row = sqlx::query!(
+            "SELECT state FROM sessions WHERE id = $1 AND expires_at > now()",
+            session_key.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to load session state")
+        .map_err(LoadError::Other)?;
+
+        row.map(|r| serde_json::from_value(r.state))
+            .transpose()
+            .context("Failed to deserialize session state")
+            .map_err(LoadError::Deserialization)
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let state = serde_json::to_value(session_state)
+            .context("Failed to serialize session state")
+            .map_err(SaveError::Serialization)?;
+        let session_key = generate_session_key();
+
+        sqlx::query!(
+            "INSERT INTO sessions (id, state, expires_at) VALUES ($1, $2, $3)",
+            session_key.as_ref(),
+            state,
+            expires_at(ttl)
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to save session state")
+        .map_err(SaveError::Other)?;
+
+        Ok(session_key)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let state = serde_json::to_value(&session_state)
+            .context("Failed to serialize session state")
+            .map_err(UpdateError::Serialization)?;
+
+        let result = sqlx::query!(
+            "UPDATE sessions SET state = $2, expires_at = $3 WHERE id = $1",
+            session_key.as_ref(),
+            state,
+            expires_at(ttl)
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to update session state")
+        .map_err(UpdateError::Other)?;
+
+        // The session expired (or was deleted) in the meantime, so a new one is started
+        if result.rows_affected() == 0 {
+            return self.save(session_state, ttl).await.map_err(|e| match e {
+                SaveError::Serialization(e) => UpdateError::Serialization(e),
+                SaveError::Other(e) => UpdateError::Other(e),
+            });
+        }
+
+        Ok(session_key)
+    }
+
+    async fn update_ttl(
+        &self,
+        session_key: &SessionKey,
+        ttl: &Duration,
+    ) -> Result<(), anyhow::Error> {
+        sqlx::query!(
+            "UPDATE sessions SET expires_at = $2 WHERE id = $1",
+            session_key.as_ref(),
+            expires_at(ttl)
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to update session TTL")?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        sqlx::query!("DELETE FROM sessions WHERE id = $1", session_key.as_ref())
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete session")?;
+
+        Ok(())
+    }
+}
+
+fn expires_at(ttl: &Duration) -> chrono::DateTime<chrono::Utc> {
+    chrono::Utc::now() + chrono::Duration::seconds(ttl.whole_seconds())
+}
+
+fn generate_session_key() -> SessionKey {
+    let mut rng = thread_rng();
+
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(64)
+        .collect::<String>()
+        .try_into()
+        .expect("A 64 characters long key is a valid session key")
+}
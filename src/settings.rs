@@ -1,5 +1,6 @@
 use std::env;
 
+use anyhow::Context;
 use config::{Config, Environment, File};
 use once_cell::sync::Lazy;
 use secrecy::{ExposeSecret, Secret};
@@ -40,11 +41,31 @@ pub struct Settings {
     pub app: AppSettings,
     pub database: DatabaseSettings,
     pub email_client: EmailClientSettings,
+    pub email_delivery: EmailDeliverySettings,
+    pub email_templates: EmailTemplatesSettings,
+    pub cleanup: CleanupSettings,
+    pub readiness: ReadinessSettings,
 }
 
 #[derive(Clone)]
 pub struct AppBaseUrl(pub reqwest::Url);
 
+#[derive(Clone)]
+pub struct HmacSecret(pub Secret<String>);
+
+#[derive(Clone)]
+pub struct SubscriptionTokenExpiry(pub chrono::Duration);
+
+impl TryFrom<std::time::Duration> for SubscriptionTokenExpiry {
+    type Error = anyhow::Error;
+
+    fn try_from(expiry: std::time::Duration) -> Result<Self, Self::Error> {
+        chrono::Duration::from_std(expiry)
+            .map(Self)
+            .context("The subscription token expiry is out of range")
+    }
+}
+
 pub fn deserialize_url_from_string<'de, D>(deserializer: D) -> Result<reqwest::Url, D::Error>
 where
     D: serde::de::Deserializer<'de>,
@@ -67,8 +88,68 @@ pub struct AppSettings {
     pub host: String,
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
+    /// Serves `/metrics` apart from the API, so that it is not exposed with it
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub metrics_port: u16,
     #[serde(deserialize_with = "deserialize_app_base_url_from_string")]
     pub base_url: AppBaseUrl,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub session_expiry_secs: u64,
+    /// How long confirmation links stay valid
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub subscription_token_expiry_secs: u64,
+    /// Signs the session and flash message cookies, required and at least 64 bytes long
+    pub cookie_signing_key: Option<Secret<String>>,
+    /// Signs the tokens of unsubscribe links, required and at least 32 bytes long
+    pub hmac_secret: Option<Secret<String>>,
+}
+
+/// The former default of `app.cookie_signing_key`, which is public.
+const PUBLIC_COOKIE_SIGNING_KEY: &str =
+    "super-long-and-secret-random-key-needed-to-verify-message-integrity";
+/// The former default of `app.hmac_secret`, which is public.
+const PUBLIC_HMAC_SECRET: &str =
+    "another-long-and-secret-random-key-needed-to-sign-unsubscribe-links";
+
+impl AppSettings {
+    /// The cookie signing key, failing if it is missing, too short or public.
+    pub fn cookie_signing_key(&self) -> Result<Secret<String>, anyhow::Error> {
+        required_secret(
+            "app.cookie_signing_key",
+            self.cookie_signing_key.as_ref(),
+            PUBLIC_COOKIE_SIGNING_KEY,
+            64,
+        )
+    }
+
+    /// The secret signing unsubscribe links, failing if it is missing, too short or public.
+    pub fn hmac_secret(&self) -> Result<Secret<String>, anyhow::Error> {
+        required_secret(
+            "app.hmac_secret",
+            self.hmac_secret.as_ref(),
+            PUBLIC_HMAC_SECRET,
+            32,
+        )
+    }
+}
+
+/// Rejects a missing secret, as well as one that anyone could use to forge signatures.
+fn required_secret(
+    name: &str,
+    secret: Option<&Secret<String>>,
+    public_value: &str,
+    min_len: usize,
+) -> Result<Secret<String>, anyhow::Error> {
+    let secret = secret.with_context(|| format!("`{name}` is required"))?;
+
+    if secret.expose_secret() == public_value {
+        anyhow::bail!("`{name}` is the former default, which is public");
+    }
+    if secret.expose_secret().len() < min_len {
+        anyhow::bail!("`{name}` must be at least {min_len} bytes long");
+    }
+
+    Ok(secret.clone())
 }
 
 #[derive(serde::Deserialize)]
@@ -81,6 +162,8 @@ pub struct DatabaseSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
     pub require_ssl: bool,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_connections: u32,
 }
 
 impl DatabaseSettings {
@@ -105,9 +188,235 @@ impl DatabaseSettings {
 #[derive(serde::Deserialize)]
 #[allow(unused)]
 pub struct EmailClientSettings {
+    /// The transport emails are sent through
+    pub provider: EmailProvider,
     pub api_key: Secret<String>,
+    /// The API's origin, e.g. `https://email.eu-west-1.amazonaws.com` for SES
     pub base_url: String,
     pub sender: crate::domain::EmailAddress,
     pub sandbox: bool,
     pub timeout_millis: u64,
+    /// Retries of failed sends to the `sendgrid`, `postmark`, `mailgun`, `ses` and `smtp` providers
+    pub retry: EmailRetrySettings,
+    pub circuit_breaker: CircuitBreakerSettings,
+    /// Required by the `mailgun` provider
+    pub mailgun: Option<MailgunSettings>,
+    /// Required by the `ses` provider
+    pub ses: Option<SesSettings>,
+    /// Required by the `smtp` provider
+    pub smtp: Option<SmtpSettings>,
+    /// Required by the `maildir` provider
+    pub maildir: Option<MaildirSettings>,
+}
+
+impl EmailClientSettings {
+    /// The host and port that the provider is reached at, if it sends over the network.
+    pub fn provider_address(&self) -> Option<(String, u16)> {
+        match self.provider {
+            EmailProvider::SendGrid
+            | EmailProvider::Postmark
+            | EmailProvider::Mailgun
+            | EmailProvider::Ses => {
+                let url = reqwest::Url::parse(&self.base_url).ok()?;
+                Some((url.host_str()?.to_string(), url.port_or_known_default()?))
+            }
+            EmailProvider::Smtp => {
+                let smtp = self.smtp.as_ref()?;
+                Some((smtp.host.clone(), smtp.port))
+            }
+            EmailProvider::Maildir | EmailProvider::Capture => None,
+        }
+    }
+}
+
+impl EmailProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::SendGrid => "sendgrid",
+            Self::Postmark => "postmark",
+            Self::Mailgun => "mailgun",
+            Self::Ses => "ses",
+            Self::Smtp => "smtp",
+            Self::Maildir => "maildir",
+            Self::Capture => "capture",
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum EmailProvider {
+    #[serde(rename = "sendgrid")]
+    SendGrid,
+    #[serde(rename = "postmark")]
+    Postmark,
+    #[serde(rename = "mailgun")]
+    Mailgun,
+    #[serde(rename = "ses")]
+    Ses,
+    #[serde(rename = "smtp")]
+    Smtp,
+    /// Writes emails as files instead of sending them
+    #[serde(rename = "maildir")]
+    Maildir,
+    /// Stores emails for the development mailbox instead of sending them
+    #[serde(rename = "capture")]
+    Capture,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[allow(unused)]
+pub struct MailgunSettings {
+    /// The sending domain, which is part of the API path
+    pub domain: String,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[allow(unused)]
+pub struct SesSettings {
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: Secret<String>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[allow(unused)]
+pub struct MaildirSettings {
+    /// The Maildir's directory, its `tmp`, `new` and `cur` subdirectories are created if missing
+    pub path: String,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[allow(unused)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+    pub tls: SmtpTls,
+    /// Authenticates with PLAIN or LOGIN when present
+    pub username: Option<String>,
+    pub password: Option<Secret<String>>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_pool_size: u32,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain text, only meant for local relays and SMTP sinks
+    None,
+    /// Upgrades the connection with STARTTLS, failing if the server does not support it
+    StartTls,
+    /// Connects over TLS right away (SMTPS)
+    Implicit,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[allow(unused)]
+pub struct EmailRetrySettings {
+    /// Attempts made per send, including the first one
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub base_backoff_millis: u64,
+    /// Also the longest `Retry-After` waited for, longer ones are left to the email delivery queue
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_backoff_millis: u64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[allow(unused)]
+pub struct CircuitBreakerSettings {
+    /// Consecutive transient failures that open the circuit
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub failure_threshold: u32,
+    /// How long sends fail fast once the circuit is open
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub open_duration_secs: u64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[allow(unused)]
+pub struct EmailDeliverySettings {
+    /// Attempts made before an email is dropped
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub base_backoff_millis: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_backoff_millis: u64,
+    /// How long the worker sleeps when there is nothing to deliver
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub poll_interval_millis: u64,
+    /// How long dequeued emails are withheld from other workers while they are sent, which has
+    /// to outlast the retries of the email client
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lease_secs: u64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[allow(unused)]
+pub struct CleanupSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub interval_secs: u64,
+    /// How long expired subscription tokens are kept, so that their links are still told apart
+    /// from invalid ones
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub subscription_token_retention_secs: u64,
+    /// How long the responses of idempotent requests are replayed for
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub idempotency_key_retention_secs: u64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[allow(unused)]
+pub struct EmailTemplatesSettings {
+    /// Holds a directory of templates per locale, e.g. `en/confirmation.html`
+    pub dir: String,
+    /// Used when the subscriber's language has no templates
+    pub default_locale: String,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[allow(unused)]
+pub struct ReadinessSettings {
+    /// How long each dependency check of `/ready` may take
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_millis: u64,
+    /// Also reports whether the email provider is reachable, which does not affect readiness as
+    /// emails are queued until it is
+    pub check_email_provider: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    use super::required_secret;
+
+    #[test]
+    fn missing_secrets_are_rejected() {
+        assert_err!(required_secret("secret", None, "public", 8));
+    }
+
+    #[test]
+    fn public_secrets_are_rejected() {
+        let secret = Secret::new("public-value".to_string());
+
+        assert_err!(required_secret("secret", Some(&secret), "public-value", 8));
+    }
+
+    #[test]
+    fn short_secrets_are_rejected() {
+        let secret = Secret::new("short".to_string());
+
+        assert_err!(required_secret("secret", Some(&secret), "public", 8));
+    }
+
+    #[test]
+    fn long_private_secrets_are_accepted() {
+        let secret = Secret::new("long-and-private".to_string());
+
+        assert_ok!(required_secret("secret", Some(&secret), "public", 8));
+    }
 }
@@ -1,40 +1,133 @@
 use std::net::TcpListener;
+use std::time::Duration;
 
+use actix_session::config::PersistentSession;
+use actix_session::SessionMiddleware;
+use actix_web::cookie::{self, Key};
 use actix_web::{dev::Server, web, App, HttpServer};
+use actix_web_flash_messages::storage::CookieMessageStore;
+use actix_web_flash_messages::FlashMessagesFramework;
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 use tracing_actix_web::TracingLogger;
 
-use crate::email::EmailClient;
-use crate::routes::{confirm_subscription, health_check, subscribe};
-use crate::settings::AppBaseUrl;
+use crate::email::EmailTemplates;
+use crate::metrics::{metrics, record_http_metrics};
+use crate::routes::{
+    admin_dashboard, confirm_subscription, dev_mailbox, dev_mailbox_email, dev_mailbox_emails,
+    health_check, log_out, login, login_form, publish_newsletter, readiness_check, subscribe,
+    unsubscribe, unsubscribe_form, ReadinessChecks, SpareDatabaseConnection,
+};
+use crate::session_store::PgSessionStore;
+use crate::settings::{AppBaseUrl, HmacSecret, SubscriptionTokenExpiry};
 
 pub static HEALTH_PATH: &str = "health";
+pub static READY_PATH: &str = "ready";
 pub static SUBSCRIPTIONS_PATH: &str = "subscriptions";
 pub static SUBSCRIPTIONS_CONFIRM_PATH: &str = "subscriptions/confirm";
+pub static SUBSCRIPTIONS_UNSUBSCRIBE_PATH: &str = "subscriptions/unsubscribe";
+pub static NEWSLETTERS_PATH: &str = "newsletters";
+pub static LOGIN_PATH: &str = "login";
+pub static ADMIN_DASHBOARD_PATH: &str = "admin/dashboard";
+pub static ADMIN_LOGOUT_PATH: &str = "admin/logout";
+pub static DEV_MAILBOX_PATH: &str = "dev/mailbox";
+pub static DEV_MAILBOX_EMAILS_PATH: &str = "dev/mailbox/emails";
+pub static METRICS_PATH: &str = "metrics";
+
+/// What the server needs besides its listener and database pool.
+pub struct ServerSettings {
+    pub app_base_url: AppBaseUrl,
+    pub cookie_signing_key: Secret<String>,
+    pub session_expiry: Duration,
+    pub hmac_secret: Secret<String>,
+    pub subscription_token_expiry: Duration,
+    pub email_templates: EmailTemplates,
+    pub readiness_checks: ReadinessChecks,
+    /// Serves the captured emails, only meant for development
+    pub enable_dev_mailbox: bool,
+}
 
 pub fn run_server(
     listener: TcpListener,
     pool: &PgPool,
-    email_client: EmailClient,
-    app_base_url: AppBaseUrl,
-) -> Result<Server, std::io::Error> {
+    settings: ServerSettings,
+) -> Result<Server, anyhow::Error> {
+    let ServerSettings {
+        app_base_url,
+        cookie_signing_key,
+        session_expiry,
+        hmac_secret,
+        subscription_token_expiry,
+        email_templates,
+        readiness_checks,
+        enable_dev_mailbox,
+    } = settings;
+
     let server = {
+        let session_store = PgSessionStore::new(pool.clone());
         let pool = web::Data::new(pool.clone());
-        let email_client = web::Data::new(email_client);
         let app_base_url = web::Data::new(app_base_url);
+        let hmac_secret = web::Data::new(HmacSecret(hmac_secret));
+        let subscription_token_expiry = web::Data::new(SubscriptionTokenExpiry::try_from(
+            subscription_token_expiry,
+        )?);
+        let email_templates = web::Data::new(email_templates);
+        let readiness_checks = web::Data::new(readiness_checks);
+        let spare_connection = web::Data::new(SpareDatabaseConnection::default());
+
+        let cookie_signing_key = Key::try_from(cookie_signing_key.expose_secret().as_bytes())
+            .context("The cookie signing key must be at least 64 bytes long")?;
+        let session_ttl = cookie::time::Duration::seconds(session_expiry.as_secs() as i64);
+        let flash_messages_framework = FlashMessagesFramework::builder(
+            CookieMessageStore::builder(cookie_signing_key.clone()).build(),
+        )
+        .build();
 
         HttpServer::new(move || {
             App::new()
+                .wrap(flash_messages_framework.clone())
+                .wrap(
+                    SessionMiddleware::builder(session_store.clone(), cookie_signing_key.clone())
+                        .session_lifecycle(PersistentSession::default().session_ttl(session_ttl))
+                        .build(),
+                )
+                .wrap_fn(record_http_metrics)
                 .wrap(TracingLogger::default())
                 .route(HEALTH_PATH, web::get().to(health_check))
+                .route(READY_PATH, web::get().to(readiness_check))
                 .route(SUBSCRIPTIONS_PATH, web::post().to(subscribe))
                 .route(
                     SUBSCRIPTIONS_CONFIRM_PATH,
                     web::get().to(confirm_subscription),
                 )
+                .route(
+                    SUBSCRIPTIONS_UNSUBSCRIBE_PATH,
+                    web::get().to(unsubscribe_form),
+                )
+                .route(SUBSCRIPTIONS_UNSUBSCRIBE_PATH, web::post().to(unsubscribe))
+                .route(NEWSLETTERS_PATH, web::post().to(publish_newsletter))
+                .route(LOGIN_PATH, web::get().to(login_form))
+                .route(LOGIN_PATH, web::post().to(login))
+                .route(ADMIN_DASHBOARD_PATH, web::get().to(admin_dashboard))
+                .route(ADMIN_LOGOUT_PATH, web::post().to(log_out))
+                .configure(|cfg| {
+                    if enable_dev_mailbox {
+                        cfg.route(DEV_MAILBOX_PATH, web::get().to(dev_mailbox))
+                            .route(DEV_MAILBOX_EMAILS_PATH, web::get().to(dev_mailbox_emails))
+                            .route(
+                                &format!("{DEV_MAILBOX_PATH}/{{id}}"),
+                                web::get().to(dev_mailbox_email),
+                            );
+                    }
+                })
                 .app_data(pool.clone())
-                .app_data(email_client.clone())
                 .app_data(app_base_url.clone())
+                .app_data(hmac_secret.clone())
+                .app_data(subscription_token_expiry.clone())
+                .app_data(email_templates.clone())
+                .app_data(readiness_checks.clone())
+                .app_data(spare_connection.clone())
         })
         .listen(listener)?
         .run()
@@ -42,3 +135,19 @@ pub fn run_server(
 
     Ok(server)
 }
+
+/// Serves the metrics on their own listener, apart from the API.
+pub fn run_metrics_server(listener: TcpListener, pool: &PgPool) -> Result<Server, std::io::Error> {
+    let pool = web::Data::new(pool.clone());
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .route(METRICS_PATH, web::get().to(metrics))
+            .app_data(pool.clone())
+    })
+    .workers(1)
+    .listen(listener)?
+    .run();
+
+    Ok(server)
+}
@@ -0,0 +1,140 @@
+use reqwest::StatusCode;
+
+use zero2prod::startup::{ADMIN_DASHBOARD_PATH, ADMIN_LOGOUT_PATH, LOGIN_PATH};
+
+use crate::login::{get_login_html, post_login};
+use crate::utils::{assert_is_redirect_to, cookie_client, spawn_server, App};
+
+async fn get_admin_dashboard(
+    client: &reqwest::Client,
+    address: &reqwest::Url,
+) -> reqwest::Response {
+    client
+        .get(format!("{address}{ADMIN_DASHBOARD_PATH}"))
+        .send()
+        .await
+        .unwrap_or_else(|_| panic!("Failed to GET {ADMIN_DASHBOARD_PATH}"))
+}
+
+async fn post_logout(client: &reqwest::Client, address: &reqwest::Url) -> reqwest::Response {
+    client
+        .post(format!("{address}{ADMIN_LOGOUT_PATH}"))
+        .send()
+        .await
+        .unwrap_or_else(|_| panic!("Failed to POST {ADMIN_LOGOUT_PATH}"))
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_access_the_admin_dashboard() {
+    // Given
+    let App { address, .. } = spawn_server().await;
+    let client = cookie_client();
+
+    // When
+    let res = get_admin_dashboard(&client, &address).await;
+
+    // Then
+    assert_is_redirect_to(&res, &format!("/{LOGIN_PATH}"));
+}
+
+#[tokio::test]
+async fn the_admin_dashboard_greets_the_logged_in_user() {
+    // Given
+    let App {
+        address, test_user, ..
+    } = spawn_server().await;
+    let client = cookie_client();
+
+    post_login(&client, &address, &test_user.username, &test_user.password).await;
+
+    // When
+    let res = get_admin_dashboard(&client, &address).await;
+
+    // Then
+    assert_eq!(StatusCode::OK, res.status());
+    let html = res.text().await.unwrap();
+    assert!(html.contains(&format!("Welcome {}", test_user.username)));
+}
+
+#[tokio::test]
+async fn the_username_is_escaped_on_the_admin_dashboard() {
+    // Given
+    let App {
+        address,
+        pool,
+        test_user,
+        ..
+    } = spawn_server().await;
+    let client = cookie_client();
+    let username = "<script>alert('admin')</script>";
+
+    sqlx::query!(
+        "UPDATE users SET username = $1 WHERE id = $2",
+        username,
+        test_user.user_id
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    post_login(&client, &address, username, &test_user.password).await;
+
+    // When
+    let html = get_admin_dashboard(&client, &address)
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Then
+    assert!(!html.contains(username));
+    assert!(html.contains("&lt;script&gt;alert(&#x27;admin&#x27;)&lt;/script&gt;"));
+}
+
+#[tokio::test]
+async fn logout_clears_session_state() {
+    // Given
+    let App {
+        address, test_user, ..
+    } = spawn_server().await;
+    let client = cookie_client();
+
+    post_login(&client, &address, &test_user.username, &test_user.password).await;
+
+    // When
+    let res = post_logout(&client, &address).await;
+
+    // Then
+    assert_is_redirect_to(&res, &format!("/{LOGIN_PATH}"));
+
+    let html = get_login_html(&client, &address).await;
+    assert!(html.contains("<p><i>You have successfully logged out.</i></p>"));
+
+    let res = get_admin_dashboard(&client, &address).await;
+    assert_is_redirect_to(&res, &format!("/{LOGIN_PATH}"));
+}
+
+#[tokio::test]
+async fn sessions_are_stored_server_side() {
+    // Given
+    let App {
+        address,
+        test_user,
+        pool,
+        ..
+    } = spawn_server().await;
+    let client = cookie_client();
+
+    // When
+    post_login(&client, &address, &test_user.username, &test_user.password).await;
+
+    // Then
+    let sessions = sqlx::query!("SELECT state FROM sessions")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(1, sessions.len());
+    assert!(sessions[0].state["user_id"]
+        .as_str()
+        .unwrap()
+        .contains(&test_user.user_id.to_string()));
+}
@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use zero2prod::cleanup_worker::{
+    delete_expired_sessions, delete_stale_idempotency_keys, delete_stale_subscription_tokens,
+};
+
+use crate::subscriptions::post_valid_body_to_subscriptions;
+use crate::utils::{spawn_server, App};
+
+async fn count_subscription_tokens(app: &App) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM subscription_tokens"#)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap()
+        .count
+}
+
+#[tokio::test]
+async fn tokens_expired_for_longer_than_the_retention_are_deleted() {
+    // Given
+    let app = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    post_valid_body_to_subscriptions(&client, &app.address).await;
+    post_valid_body_to_subscriptions(&client, &app.address).await;
+
+    sqlx::query!(
+        r#"
+        UPDATE subscription_tokens SET expires_at = now() - interval '2 hours'
+        WHERE token_hash = (SELECT token_hash FROM subscription_tokens LIMIT 1)
+        "#
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    // When
+    let n_deleted = delete_stale_subscription_tokens(&app.pool, Duration::from_secs(3600))
+        .await
+        .unwrap();
+
+    // Then
+    assert_eq!(1, n_deleted);
+    assert_eq!(1, count_subscription_tokens(&app).await);
+}
+
+#[tokio::test]
+async fn recently_expired_tokens_are_kept() {
+    // Given
+    let app = spawn_server().await;
+
+    post_valid_body_to_subscriptions(&reqwest::Client::new(), &app.address).await;
+
+    sqlx::query!("UPDATE subscription_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    // When
+    let n_deleted = delete_stale_subscription_tokens(&app.pool, Duration::from_secs(3600))
+        .await
+        .unwrap();
+
+    // Then
+    assert_eq!(0, n_deleted);
+    assert_eq!(1, count_subscription_tokens(&app).await);
+}
+
+#[tokio::test]
+async fn expired_sessions_are_deleted() {
+    // Given
+    let app = spawn_server().await;
+
+    for (id, expires_in_mins) in [("expired", -1), ("active", 60)] {
+        sqlx::query!(
+            r#"
+            INSERT INTO sessions (id, state, expires_at)
+            VALUES ($1, '{}', now() + make_interval(mins => $2))
+            "#,
+            id,
+            expires_in_mins
+        )
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    }
+
+    // When
+    let n_deleted = delete_expired_sessions(&app.pool).await.unwrap();
+
+    // Then
+    assert_eq!(1, n_deleted);
+    let remaining = sqlx::query!("SELECT id FROM sessions")
+        .fetch_all(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        vec!["active"],
+        remaining.iter().map(|s| s.id.as_str()).collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+async fn idempotency_keys_older_than_the_retention_are_deleted() {
+    // Given
+    let app = spawn_server().await;
+
+    for (key, age_mins) in [("stale", 120), ("recent", 1)] {
+        sqlx::query!(
+            r#"
+            INSERT INTO idempotency (user_id, idempotency_key, created_at)
+            VALUES ($1, $2, now() - make_interval(mins => $3))
+            "#,
+            app.test_user.user_id,
+            key,
+            age_mins
+        )
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    }
+
+    // When
+    let n_deleted = delete_stale_idempotency_keys(&app.pool, Duration::from_secs(3600))
+        .await
+        .unwrap();
+
+    // Then
+    assert_eq!(1, n_deleted);
+    let keys = sqlx::query_scalar!("SELECT idempotency_key FROM idempotency")
+        .fetch_all(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(vec!["recent".to_string()], keys);
+}
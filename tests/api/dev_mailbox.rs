@@ -0,0 +1,111 @@
+use reqwest::StatusCode;
+use uuid::Uuid;
+
+use zero2prod::startup::{DEV_MAILBOX_EMAILS_PATH, DEV_MAILBOX_PATH};
+
+use crate::subscriptions::post_valid_body_to_subscriptions;
+use crate::utils::{links, spawn_server, App};
+
+#[derive(serde::Deserialize)]
+struct CapturedEmail {
+    id: Uuid,
+    recipient: String,
+    subject: String,
+    html_content: String,
+    text_content: String,
+}
+
+async fn get_captured_emails(app: &App, to: &str) -> Vec<CapturedEmail> {
+    reqwest::Client::new()
+        .get(format!("{}{DEV_MAILBOX_EMAILS_PATH}", app.address))
+        .query(&[("to", to)])
+        .send()
+        .await
+        .unwrap_or_else(|_| panic!("Failed to GET {DEV_MAILBOX_EMAILS_PATH}"))
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn captured_emails_are_listed_in_the_mailbox() {
+    // Given
+    let app = spawn_server().await;
+    let (_, subscriber) =
+        post_valid_body_to_subscriptions(&reqwest::Client::new(), &app.address).await;
+
+    // When
+    app.capture_all_pending_emails().await;
+
+    // Then
+    let emails = get_captured_emails(&app, subscriber.email.as_ref()).await;
+    assert_eq!(1, emails.len());
+    assert_eq!(subscriber.email.as_ref(), emails[0].recipient);
+    assert_eq!("Welcome!", emails[0].subject);
+    assert!(emails[0].text_content.contains("Welcome to my newsletter"));
+
+    let html = reqwest::get(format!("{}{DEV_MAILBOX_PATH}", app.address))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains(&format!("/{DEV_MAILBOX_PATH}/{}", emails[0].id)));
+}
+
+#[tokio::test]
+async fn the_confirmation_link_can_be_followed_from_the_mailbox() {
+    // Given
+    let app = spawn_server().await;
+    let (_, subscriber) =
+        post_valid_body_to_subscriptions(&reqwest::Client::new(), &app.address).await;
+    app.capture_all_pending_emails().await;
+
+    let emails = get_captured_emails(&app, subscriber.email.as_ref()).await;
+    let confirmation_link = links(&emails[0].html_content)[0].as_str().to_owned();
+
+    // When
+    let res = reqwest::get(confirmation_link).await.unwrap();
+
+    // Then
+    assert_eq!(StatusCode::OK, res.status());
+}
+
+#[tokio::test]
+async fn a_captured_email_is_rendered_as_it_was_sent() {
+    // Given
+    let app = spawn_server().await;
+    let (_, subscriber) =
+        post_valid_body_to_subscriptions(&reqwest::Client::new(), &app.address).await;
+    app.capture_all_pending_emails().await;
+    let email = get_captured_emails(&app, subscriber.email.as_ref())
+        .await
+        .remove(0);
+
+    // When
+    let res = reqwest::get(format!("{}{DEV_MAILBOX_PATH}/{}", app.address, email.id))
+        .await
+        .unwrap();
+
+    // Then
+    assert_eq!(StatusCode::OK, res.status());
+    assert!(res.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    assert_eq!(email.html_content, res.text().await.unwrap());
+}
+
+#[tokio::test]
+async fn an_unknown_email_is_not_found() {
+    // Given
+    let App { address, .. } = spawn_server().await;
+
+    // When
+    let res = reqwest::get(format!("{address}{DEV_MAILBOX_PATH}/{}", Uuid::new_v4()))
+        .await
+        .unwrap();
+
+    // Then
+    assert_eq!(StatusCode::NOT_FOUND, res.status());
+}
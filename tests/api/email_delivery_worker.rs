@@ -0,0 +1,300 @@
+use std::time::Duration;
+
+use fake::faker::internet::en::SafeEmail;
+use fake::faker::lorem::en::{Paragraph, Sentence};
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use secrecy::Secret;
+use uuid::Uuid;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+use zero2prod::domain::EmailAddress;
+use zero2prod::email::{EmailData, RetryingEmailSender, SendGridClient};
+use zero2prod::email_delivery_queue::{dequeue_tasks, enqueue_email};
+use zero2prod::settings::{CircuitBreakerSettings, EmailRetrySettings, SETTINGS};
+
+use crate::subscriptions::base_send_grid_send_endpoint_mock;
+use crate::utils::{spawn_server, App};
+
+async fn enqueue_fake_email(app: &App) -> Uuid {
+    let email = EmailData {
+        to: EmailAddress::parse(SafeEmail().fake()).unwrap(),
+        subject: Sentence(1..2).fake(),
+        html_content: Paragraph(1..10).fake(),
+        text_content: None,
+        unsubscribe_url: None,
+    };
+
+    enqueue_email(&app.pool, &email).await.unwrap()
+}
+
+struct QueuedTask {
+    n_attempts: i32,
+    execute_after: chrono::DateTime<chrono::Utc>,
+}
+
+async fn get_queued_task(app: &App, task_id: Uuid) -> Option<QueuedTask> {
+    sqlx::query_as!(
+        QueuedTask,
+        "SELECT n_attempts, execute_after FROM email_delivery_queue WHERE id = $1",
+        task_id
+    )
+    .fetch_optional(&app.pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn delivered_emails_are_removed_from_the_queue() {
+    // Given
+    let app = spawn_server().await;
+    let task_id = enqueue_fake_email(&app).await;
+
+    base_send_grid_send_endpoint_mock()
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert!(get_queued_task(&app, task_id).await.is_none());
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_later() {
+    // Given
+    let app = spawn_server().await;
+    let task_id = enqueue_fake_email(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(StatusCode::INTERNAL_SERVER_ERROR))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    let task = get_queued_task(&app, task_id)
+        .await
+        .expect("The task should still be queued");
+    assert_eq!(1, task.n_attempts);
+    assert!(task.execute_after > chrono::Utc::now());
+}
+
+#[tokio::test]
+async fn retried_deliveries_are_sent_once_due() {
+    // Given
+    let app = spawn_server().await;
+    let task_id = enqueue_fake_email(&app).await;
+
+    let failing_email_server_guard = Mock::given(any())
+        .respond_with(ResponseTemplate::new(StatusCode::INTERNAL_SERVER_ERROR))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+    drop(failing_email_server_guard);
+
+    sqlx::query!(
+        "UPDATE email_delivery_queue SET execute_after = now() WHERE id = $1",
+        task_id
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    base_send_grid_send_endpoint_mock()
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert!(get_queued_task(&app, task_id).await.is_none());
+}
+
+#[tokio::test]
+async fn deliveries_are_dropped_after_the_maximum_number_of_attempts() {
+    // Given
+    let app = spawn_server().await;
+    let task_id = enqueue_fake_email(&app).await;
+
+    sqlx::query!(
+        "UPDATE email_delivery_queue SET n_attempts = $2 WHERE id = $1",
+        task_id,
+        SETTINGS.email_delivery.max_attempts as i32 - 1
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(StatusCode::INTERNAL_SERVER_ERROR))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert!(get_queued_task(&app, task_id).await.is_none());
+}
+
+#[tokio::test]
+async fn deliveries_to_rejected_recipients_are_not_retried() {
+    // Given
+    let app = spawn_server().await;
+    let task_id = enqueue_fake_email(&app).await;
+
+    Mock::given(any())
+        .respond_with(
+            ResponseTemplate::new(StatusCode::BAD_REQUEST).set_body_json(serde_json::json!({
+                "errors": [{
+                    "message": "Does not contain a valid address.",
+                    "field": "personalizations.0.to.0.email",
+                }]
+            })),
+        )
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert!(get_queued_task(&app, task_id).await.is_none());
+}
+
+#[tokio::test]
+async fn deliveries_with_an_invalid_unsubscribe_url_are_dropped() {
+    // Given
+    let app = spawn_server().await;
+    let task_id = enqueue_fake_email(&app).await;
+    sqlx::query!(
+        "UPDATE email_delivery_queue SET unsubscribe_url = 'not a url' WHERE id = $1",
+        task_id
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(StatusCode::OK))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert!(get_queued_task(&app, task_id).await.is_none());
+}
+
+#[tokio::test]
+async fn dequeued_deliveries_are_not_dequeued_again_until_their_lease_expires() {
+    // Given
+    let app = spawn_server().await;
+    let task_id = enqueue_fake_email(&app).await;
+    let lease_expires_at = chrono::Utc::now() + chrono::Duration::minutes(5);
+
+    // When
+    let tasks = dequeue_tasks(&app.pool, 1, lease_expires_at)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Then
+    assert_eq!(task_id, tasks[0].id);
+    assert!(dequeue_tasks(&app.pool, 1, lease_expires_at)
+        .await
+        .unwrap()
+        .is_none());
+    let task = get_queued_task(&app, task_id).await.unwrap();
+    assert_eq!(0, task.n_attempts);
+    assert_eq!(lease_expires_at.timestamp(), task.execute_after.timestamp());
+}
+
+#[tokio::test]
+async fn rate_limited_deliveries_wait_for_the_retry_after() {
+    // Given
+    let app = spawn_server().await;
+    let task_id = enqueue_fake_email(&app).await;
+
+    Mock::given(any())
+        .respond_with(
+            ResponseTemplate::new(StatusCode::TOO_MANY_REQUESTS)
+                .insert_header("Retry-After", "86400"),
+        )
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    let task = get_queued_task(&app, task_id)
+        .await
+        .expect("The task should still be queued");
+    assert!(task.execute_after > chrono::Utc::now() + chrono::Duration::hours(23));
+}
+
+#[tokio::test]
+async fn deliveries_are_not_counted_as_attempts_while_the_circuit_is_open() {
+    // Given
+    let app = spawn_server().await;
+    let task_id = enqueue_fake_email(&app).await;
+    let email_sender = RetryingEmailSender::new(
+        Box::new(SendGridClient::new(
+            app.email_server.uri(),
+            Secret::new(Faker.fake()),
+            EmailAddress::parse(SafeEmail().fake()).unwrap(),
+            Duration::from_secs(2),
+            true,
+        )),
+        EmailRetrySettings {
+            max_attempts: 1,
+            base_backoff_millis: 10,
+            max_backoff_millis: 100,
+        },
+        &CircuitBreakerSettings {
+            failure_threshold: 1,
+            open_duration_secs: 3600,
+        },
+    );
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(StatusCode::INTERNAL_SERVER_ERROR))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails_with(&email_sender).await;
+
+    sqlx::query!(
+        "UPDATE email_delivery_queue SET execute_after = now() WHERE id = $1",
+        task_id
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    // When
+    app.dispatch_all_pending_emails_with(&email_sender).await;
+
+    // Then
+    let task = get_queued_task(&app, task_id)
+        .await
+        .expect("The task should still be queued");
+    assert_eq!(1, task.n_attempts);
+    assert!(task.execute_after > chrono::Utc::now() + chrono::Duration::minutes(59));
+}
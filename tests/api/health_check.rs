@@ -1,4 +1,7 @@
-use zero2prod::startup::HEALTH_PATH;
+use reqwest::StatusCode;
+use sqlx::{Connection, PgConnection};
+use zero2prod::settings::SETTINGS;
+use zero2prod::startup::{HEALTH_PATH, READY_PATH};
 
 use crate::utils::{spawn_server, App};
 
@@ -17,3 +20,108 @@ async fn health_check_works() {
     assert!(res.status().is_success());
     assert_eq!(Some(0), res.content_length());
 }
+
+#[tokio::test]
+async fn readiness_reports_every_dependency() {
+    // Given
+    let App { address, .. } = spawn_server().await;
+
+    // When
+    let res = reqwest::get(format!("{address}{READY_PATH}"))
+        .await
+        .unwrap();
+
+    // Then
+    assert_eq!(StatusCode::OK, res.status());
+
+    let report: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(true, report["ready"]);
+    assert_eq!(true, report["checks"]["database"]["up"]);
+    assert_eq!(true, report["checks"]["database_pool"]["up"]);
+    assert_eq!(true, report["checks"]["email_provider"]["up"]);
+}
+
+#[tokio::test]
+async fn readiness_holds_while_the_database_pool_is_saturated() {
+    // Given
+    let App { address, pool, .. } = spawn_server().await;
+    let mut connections = Vec::new();
+    for _ in 0..SETTINGS.database.max_connections {
+        connections.push(pool.acquire().await.unwrap());
+    }
+
+    // When
+    let res = reqwest::get(format!("{address}{READY_PATH}"))
+        .await
+        .unwrap();
+
+    // Then
+    assert_eq!(StatusCode::OK, res.status());
+
+    let report: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(true, report["checks"]["database"]["up"]);
+    assert_eq!(false, report["checks"]["database_pool"]["up"]);
+    assert_eq!(false, report["checks"]["database_pool"]["required"]);
+    assert!(report["checks"]["database_pool"]
+        .get("duration_millis")
+        .is_none());
+}
+
+#[tokio::test]
+async fn readiness_opens_a_single_connection_besides_a_saturated_pool() {
+    // Given
+    let App { address, pool, .. } = spawn_server().await;
+    let mut connections = Vec::new();
+    for _ in 0..SETTINGS.database.max_connections {
+        connections.push(pool.acquire().await.unwrap());
+    }
+
+    // When
+    for _ in 0..3 {
+        let res = reqwest::get(format!("{address}{READY_PATH}"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    // Then
+    let mut connection = PgConnection::connect_with(pool.connect_options())
+        .await
+        .unwrap();
+    let n_connections = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM pg_stat_activity WHERE datname = current_database()"#
+    )
+    .fetch_one(&mut connection)
+    .await
+    .unwrap();
+    // The pool's, the spare one and this one
+    assert_eq!(SETTINGS.database.max_connections as i64 + 2, n_connections);
+}
+
+#[tokio::test]
+async fn readiness_fails_while_the_database_is_unreachable() {
+    // Given
+    let App { address, pool, .. } = spawn_server().await;
+    pool.close().await;
+
+    // When
+    let res = reqwest::get(format!("{address}{READY_PATH}"))
+        .await
+        .unwrap();
+
+    // Then
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, res.status());
+
+    let report: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(false, report["ready"]);
+    assert_eq!(false, report["checks"]["database"]["up"]);
+    // The details are logged instead
+    assert_eq!(
+        serde_json::json!(["duration_millis", "required", "up"]),
+        serde_json::json!(report["checks"]["database"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .collect::<Vec<_>>())
+    );
+}
@@ -0,0 +1,119 @@
+use reqwest::StatusCode;
+use secrecy::Secret;
+
+use zero2prod::authentication::{store_credentials, Credentials};
+use zero2prod::startup::{ADMIN_DASHBOARD_PATH, LOGIN_PATH};
+
+use crate::utils::{assert_is_redirect_to, cookie_client, spawn_server, App, TestUser};
+
+pub async fn post_login(
+    client: &reqwest::Client,
+    address: &reqwest::Url,
+    username: &str,
+    password: &str,
+) -> reqwest::Response {
+    client
+        .post(format!("{address}{LOGIN_PATH}"))
+        .form(&[("username", username), ("password", password)])
+        .send()
+        .await
+        .unwrap_or_else(|_| panic!("Failed to POST {LOGIN_PATH}"))
+}
+
+pub async fn get_login_html(client: &reqwest::Client, address: &reqwest::Url) -> String {
+    client
+        .get(format!("{address}{LOGIN_PATH}"))
+        .send()
+        .await
+        .unwrap_or_else(|_| panic!("Failed to GET {LOGIN_PATH}"))
+        .text()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn login_form_is_served() {
+    // Given
+    let App { address, .. } = spawn_server().await;
+    let client = cookie_client();
+
+    // When
+    let res = client
+        .get(format!("{address}{LOGIN_PATH}"))
+        .send()
+        .await
+        .unwrap_or_else(|_| panic!("Failed to GET {LOGIN_PATH}"));
+
+    // Then
+    assert_eq!(StatusCode::OK, res.status());
+    assert!(res.text().await.unwrap().contains("<form"));
+}
+
+#[tokio::test]
+async fn an_error_flash_message_is_set_on_failure() {
+    // Given
+    let App { address, .. } = spawn_server().await;
+    let client = cookie_client();
+    let unknown_user = TestUser::generate();
+
+    // When
+    let res = post_login(
+        &client,
+        &address,
+        &unknown_user.username,
+        &unknown_user.password,
+    )
+    .await;
+
+    // Then
+    assert_is_redirect_to(&res, &format!("/{LOGIN_PATH}"));
+
+    let html = get_login_html(&client, &address).await;
+    assert!(html.contains("<p><i>Authentication failed.</i></p>"));
+
+    // The flash message is gone after it has been displayed once
+    let html = get_login_html(&client, &address).await;
+    assert!(!html.contains("Authentication failed."));
+}
+
+#[tokio::test]
+async fn redirect_to_admin_dashboard_after_login_success() {
+    // Given
+    let App {
+        address, test_user, ..
+    } = spawn_server().await;
+    let client = cookie_client();
+
+    // When
+    let res = post_login(&client, &address, &test_user.username, &test_user.password).await;
+
+    // Then
+    assert_is_redirect_to(&res, &format!("/{ADMIN_DASHBOARD_PATH}"));
+}
+
+#[tokio::test]
+async fn provisioned_admins_can_log_in_with_their_latest_password() {
+    // Given
+    let App { address, pool, .. } = spawn_server().await;
+    let client = cookie_client();
+    let credentials = |password: &str| Credentials {
+        username: "admin".into(),
+        password: Secret::new(password.into()),
+    };
+
+    let user_id = store_credentials(credentials("first-password"), &pool)
+        .await
+        .unwrap();
+    let reset_user_id = store_credentials(credentials("second-password"), &pool)
+        .await
+        .unwrap();
+
+    // When
+    let old_res = post_login(&client, &address, "admin", "first-password").await;
+    let res = post_login(&client, &address, "admin", "second-password").await;
+
+    // Then
+    assert_eq!(user_id, reset_user_id);
+    assert_is_redirect_to(&old_res, &format!("/{LOGIN_PATH}"));
+    assert_is_redirect_to(&res, &format!("/{ADMIN_DASHBOARD_PATH}"));
+}
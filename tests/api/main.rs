@@ -1,4 +1,12 @@
+mod admin_dashboard;
+mod cleanup_worker;
+mod dev_mailbox;
+mod email_delivery_worker;
 mod health_check;
+mod login;
+mod metrics;
+mod newsletters;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
 mod utils;
@@ -0,0 +1,83 @@
+use reqwest::StatusCode;
+use zero2prod::startup::{HEALTH_PATH, METRICS_PATH};
+
+use crate::subscriptions_confirm::subscribe_and_get_confirmation_link;
+use crate::utils::{spawn_server, App};
+
+async fn get_metrics(app: &App) -> String {
+    let res = reqwest::get(format!("{}{METRICS_PATH}", app.metrics_address))
+        .await
+        .unwrap_or_else(|_| panic!("Failed to GET {METRICS_PATH}"));
+    assert_eq!(StatusCode::OK, res.status());
+
+    res.text().await.unwrap()
+}
+
+/// The value of the sample with the exact name and labels, e.g. `requests_total{route="/"}`.
+fn sample(metrics: &str, name_and_labels: &str) -> Option<f64> {
+    metrics
+        .lines()
+        .find_map(|line| line.strip_prefix(name_and_labels)?.trim().parse().ok())
+}
+
+#[tokio::test]
+async fn metrics_are_served_apart_from_the_api() {
+    // Given
+    let app = spawn_server().await;
+
+    // When
+    let res = reqwest::get(format!("{}{METRICS_PATH}", app.address))
+        .await
+        .unwrap();
+
+    // Then
+    assert_eq!(StatusCode::NOT_FOUND, res.status());
+    assert!(get_metrics(&app)
+        .await
+        .contains("zero2prod_db_pool_connections"));
+}
+
+#[tokio::test]
+async fn requests_are_counted_and_timed_by_route() {
+    // Given
+    let app = spawn_server().await;
+
+    // When
+    reqwest::get(format!("{}{HEALTH_PATH}", app.address))
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Then
+    let metrics = get_metrics(&app).await;
+    let requests = sample(
+        &metrics,
+        r#"zero2prod_http_requests_total{method="GET",route="/health",status="200"}"#,
+    );
+    assert!(requests.unwrap_or_default() >= 1.0, "{metrics}");
+    let latencies = sample(
+        &metrics,
+        r#"zero2prod_http_request_duration_seconds_count{method="GET",route="/health"}"#,
+    );
+    assert!(latencies.unwrap_or_default() >= 1.0, "{metrics}");
+}
+
+#[tokio::test]
+async fn created_and_confirmed_subscriptions_are_counted() {
+    // Given
+    let app = spawn_server().await;
+    let confirmation_link = subscribe_and_get_confirmation_link(&app).await;
+
+    // When
+    reqwest::get(confirmation_link.as_str())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Then
+    let metrics = get_metrics(&app).await;
+    assert!(sample(&metrics, "zero2prod_subscriptions_created_total").unwrap_or_default() >= 1.0);
+    assert!(sample(&metrics, "zero2prod_subscriptions_confirmed_total").unwrap_or_default() >= 1.0);
+}
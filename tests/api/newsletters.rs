@@ -0,0 +1,493 @@
+use reqwest::StatusCode;
+use uuid::Uuid;
+use wiremock::matchers::any;
+
+use wiremock::{Mock, ResponseTemplate};
+
+use zero2prod::domain::SubscriptionStatus;
+use zero2prod::email::send_grid;
+use zero2prod::idempotency::IDEMPOTENCY_KEY_HEADER;
+use zero2prod::startup::{NEWSLETTERS_PATH, SUBSCRIPTIONS_UNSUBSCRIBE_PATH};
+
+use crate::login::post_login;
+use crate::subscriptions::{base_send_grid_send_endpoint_mock, post_valid_body_to_subscriptions};
+use crate::utils::{cookie_client, links, spawn_server, App, TestUser};
+
+async fn post_to_newsletters(
+    app: &App,
+    client: &reqwest::Client,
+    body: &serde_json::Value,
+) -> reqwest::Response {
+    post_to_newsletters_with_idempotency_key(app, client, body, &Uuid::new_v4().to_string()).await
+}
+
+async fn post_to_newsletters_with_idempotency_key(
+    app: &App,
+    client: &reqwest::Client,
+    body: &serde_json::Value,
+    idempotency_key: &str,
+) -> reqwest::Response {
+    let App {
+        address, test_user, ..
+    } = app;
+
+    client
+        .post(format!("{address}{NEWSLETTERS_PATH}"))
+        .basic_auth(&test_user.username, Some(&test_user.password))
+        .header(IDEMPOTENCY_KEY_HEADER, idempotency_key)
+        .json(body)
+        .send()
+        .await
+        .unwrap_or_else(|_| panic!("Failed to POST {NEWSLETTERS_PATH}"))
+}
+
+fn valid_newsletter_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "html": "<p>Newsletter body as HTML</p>",
+            "text": "Newsletter body as plain text",
+        }
+    })
+}
+
+async fn create_unconfirmed_subscriber(app: &App, client: &reqwest::Client) -> reqwest::Url {
+    let _mock_guard = base_send_grid_send_endpoint_mock()
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    post_valid_body_to_subscriptions(client, &app.address).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email_body: send_grid::MailSendBody = serde_json::from_slice(&email_request.body).unwrap();
+    let email_links = links(&email_body.content[1].value);
+
+    let mut confirmation_link = reqwest::Url::parse(email_links[0].as_str()).unwrap();
+    confirmation_link.set_port(app.address.port()).unwrap();
+
+    confirmation_link
+}
+
+async fn create_confirmed_subscriber(app: &App, client: &reqwest::Client) {
+    let confirmation_link = create_unconfirmed_subscriber(app, client).await;
+
+    client
+        .get(confirmation_link.as_str())
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
+    // Given
+    let app = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    create_unconfirmed_subscriber(&app, &client).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(StatusCode::OK))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    let res = post_to_newsletters(&app, &client, &valid_newsletter_body()).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert_eq!(StatusCode::ACCEPTED, res.status());
+}
+
+#[tokio::test]
+async fn newsletters_are_delivered_to_confirmed_subscribers() {
+    // Given
+    let app = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    create_confirmed_subscriber(&app, &client).await;
+
+    base_send_grid_send_endpoint_mock()
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    let res = post_to_newsletters(&app, &client, &valid_newsletter_body()).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert_eq!(StatusCode::ACCEPTED, res.status());
+}
+
+#[tokio::test]
+async fn newsletters_are_sent_in_batches_of_the_max_personalizations() {
+    // Given
+    let app = spawn_server().await;
+    let client = reqwest::Client::new();
+    let n_subscribers = send_grid::MAX_PERSONALIZATIONS + 1;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        SELECT gen_random_uuid(), 'subscriber' || i || '@example.com', 'Subscriber', now(), $2
+        FROM generate_series(1, $1) AS i
+        "#,
+        n_subscribers as i32,
+        SubscriptionStatus::Confirmed as _
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    base_send_grid_send_endpoint_mock()
+        .expect(n_subscribers.div_ceil(send_grid::MAX_PERSONALIZATIONS) as u64)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    let res = post_to_newsletters(&app, &client, &valid_newsletter_body()).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert_eq!(StatusCode::ACCEPTED, res.status());
+    let mut unsubscribe_urls = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .flat_map(|request| {
+            let body: send_grid::MailSendBody = serde_json::from_slice(&request.body).unwrap();
+            let html_content = body.content[1].value.to_string();
+            body.personalizations
+                .into_iter()
+                .map(|personalization| {
+                    personalization
+                        .substitutions
+                        .unwrap()
+                        .into_iter()
+                        .fold(html_content.clone(), |content, (key, value)| {
+                            content.replace(&key, &value)
+                        })
+                })
+                .collect::<Vec<_>>()
+        })
+        .map(|html_content| {
+            links(&html_content)
+                .into_iter()
+                .find(|link| link.as_str().contains(SUBSCRIPTIONS_UNSUBSCRIBE_PATH))
+                .expect("Every email should have an unsubscribe link")
+                .as_str()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    unsubscribe_urls.sort();
+    unsubscribe_urls.dedup();
+    assert_eq!(n_subscribers, unsubscribe_urls.len());
+}
+
+#[tokio::test]
+async fn newsletters_skip_confirmed_subscribers_with_invalid_emails() {
+    // Given
+    let app = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    create_confirmed_subscriber(&app, &client).await;
+
+    sqlx::query!(
+        "INSERT INTO subscriptions VALUES ($1, $2, $3, $4, $5)",
+        Uuid::new_v4(),
+        "not-an-email",
+        "Invalid",
+        chrono::Utc::now(),
+        SubscriptionStatus::Confirmed as _
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    base_send_grid_send_endpoint_mock()
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    let res = post_to_newsletters(&app, &client, &valid_newsletter_body()).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert_eq!(StatusCode::ACCEPTED, res.status());
+}
+
+#[tokio::test]
+async fn publish_newsletter_with_invalid_data_should_fail() {
+    // Given
+    let app = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    let invalid_bodies = vec![
+        (
+            serde_json::json!({
+                "content": {
+                    "html": "<p>Newsletter body as HTML</p>",
+                    "text": "Newsletter body as plain text",
+                }
+            }),
+            "missing the title",
+        ),
+        (
+            serde_json::json!({ "title": "Newsletter title" }),
+            "missing the content",
+        ),
+        (
+            serde_json::json!({
+                "title": "Newsletter title",
+                "content": { "html": "<p>Newsletter body as HTML</p>" }
+            }),
+            "missing the plain text content",
+        ),
+    ];
+
+    for (body, desc) in invalid_bodies {
+        // When
+        let res = post_to_newsletters(&app, &client, &body).await;
+
+        // Then
+        assert_eq!(
+            StatusCode::BAD_REQUEST,
+            res.status(),
+            "Should fail with BAD_REQUEST when body is {desc}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn requests_missing_authorization_are_rejected() {
+    // Given
+    let App { address, .. } = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    // When
+    let res = client
+        .post(format!("{address}{NEWSLETTERS_PATH}"))
+        .header(IDEMPOTENCY_KEY_HEADER, Uuid::new_v4().to_string())
+        .json(&valid_newsletter_body())
+        .send()
+        .await
+        .unwrap_or_else(|_| panic!("Failed to POST {NEWSLETTERS_PATH}"));
+
+    // Then
+    assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        res.headers()["WWW-Authenticate"]
+    );
+}
+
+#[tokio::test]
+async fn requests_with_invalid_credentials_are_rejected() {
+    // Given
+    let app = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    let unknown_user = TestUser::generate();
+    let invalid_credentials = vec![
+        (
+            unknown_user.username,
+            unknown_user.password,
+            "the user does not exist",
+        ),
+        (
+            app.test_user.username.clone(),
+            TestUser::generate().password,
+            "the password is wrong",
+        ),
+    ];
+
+    for (username, password, desc) in invalid_credentials {
+        // When
+        let res = client
+            .post(format!("{}{NEWSLETTERS_PATH}", app.address))
+            .basic_auth(username, Some(password))
+            .header(IDEMPOTENCY_KEY_HEADER, Uuid::new_v4().to_string())
+            .json(&valid_newsletter_body())
+            .send()
+            .await
+            .unwrap_or_else(|_| panic!("Failed to POST {NEWSLETTERS_PATH}"));
+
+        // Then
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            res.status(),
+            "Should fail with UNAUTHORIZED when {desc}"
+        );
+        assert_eq!(
+            r#"Basic realm="publish""#,
+            res.headers()["WWW-Authenticate"]
+        );
+    }
+}
+
+#[tokio::test]
+async fn logged_in_users_can_publish_without_credentials() {
+    // Given
+    let app = spawn_server().await;
+    let client = cookie_client();
+
+    create_confirmed_subscriber(&app, &client).await;
+    post_login(
+        &client,
+        &app.address,
+        &app.test_user.username,
+        &app.test_user.password,
+    )
+    .await;
+
+    base_send_grid_send_endpoint_mock()
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    let res = client
+        .post(format!("{}{NEWSLETTERS_PATH}", app.address))
+        .header(IDEMPOTENCY_KEY_HEADER, Uuid::new_v4().to_string())
+        .json(&valid_newsletter_body())
+        .send()
+        .await
+        .unwrap_or_else(|_| panic!("Failed to POST {NEWSLETTERS_PATH}"));
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert_eq!(StatusCode::ACCEPTED, res.status());
+}
+
+#[tokio::test]
+async fn requests_missing_the_idempotency_key_are_rejected() {
+    // Given
+    let app = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    // When
+    let res = client
+        .post(format!("{}{NEWSLETTERS_PATH}", app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .json(&valid_newsletter_body())
+        .send()
+        .await
+        .unwrap_or_else(|_| panic!("Failed to POST {NEWSLETTERS_PATH}"));
+
+    // Then
+    assert_eq!(StatusCode::BAD_REQUEST, res.status());
+}
+
+#[tokio::test]
+async fn newsletter_publishing_is_idempotent() {
+    // Given
+    let app = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    create_confirmed_subscriber(&app, &client).await;
+
+    base_send_grid_send_endpoint_mock()
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let idempotency_key = Uuid::new_v4().to_string();
+    let body = valid_newsletter_body();
+
+    // When
+    let first_res =
+        post_to_newsletters_with_idempotency_key(&app, &client, &body, &idempotency_key).await;
+    let second_res =
+        post_to_newsletters_with_idempotency_key(&app, &client, &body, &idempotency_key).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert_eq!(StatusCode::ACCEPTED, first_res.status());
+    assert_eq!(first_res.status(), second_res.status());
+    assert_eq!(
+        first_res.text().await.unwrap(),
+        second_res.text().await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn reusing_an_idempotency_key_for_another_newsletter_is_rejected() {
+    // Given
+    let app = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    create_confirmed_subscriber(&app, &client).await;
+
+    base_send_grid_send_endpoint_mock()
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let idempotency_key = Uuid::new_v4().to_string();
+    let mut other_body = valid_newsletter_body();
+    other_body["title"] = "Another newsletter title".into();
+
+    // When
+    let first_res = post_to_newsletters_with_idempotency_key(
+        &app,
+        &client,
+        &valid_newsletter_body(),
+        &idempotency_key,
+    )
+    .await;
+    let second_res =
+        post_to_newsletters_with_idempotency_key(&app, &client, &other_body, &idempotency_key)
+            .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert_eq!(StatusCode::ACCEPTED, first_res.status());
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, second_res.status());
+}
+
+#[tokio::test]
+async fn concurrent_newsletter_publishing_is_handled_gracefully() {
+    // Given
+    let app = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    create_confirmed_subscriber(&app, &client).await;
+
+    base_send_grid_send_endpoint_mock()
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let idempotency_key = Uuid::new_v4().to_string();
+    let body = valid_newsletter_body();
+
+    // When
+    let (first_res, second_res) = tokio::join!(
+        post_to_newsletters_with_idempotency_key(&app, &client, &body, &idempotency_key),
+        post_to_newsletters_with_idempotency_key(&app, &client, &body, &idempotency_key),
+    );
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert_eq!(StatusCode::ACCEPTED, first_res.status());
+    assert_eq!(first_res.status(), second_res.status());
+    assert_eq!(
+        first_res.text().await.unwrap(),
+        second_res.text().await.unwrap()
+    );
+}
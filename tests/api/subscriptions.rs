@@ -10,6 +10,7 @@ use wiremock::{Mock, ResponseTemplate};
 
 use zero2prod::domain::{RawSubscriber, Subscriber, SubscriptionStatus};
 use zero2prod::email::send_grid;
+use zero2prod::routes::VALIDATION_PROBLEM_TYPE;
 use zero2prod::startup::{SUBSCRIPTIONS_CONFIRM_PATH, SUBSCRIPTIONS_PATH};
 
 use crate::utils::{links, spawn_server, App};
@@ -53,11 +54,13 @@ pub fn base_send_grid_send_endpoint_mock() -> Mock {
 #[tokio::test]
 async fn subscribe_with_valid_data_should_create_pending_subscription() {
     // Given
+    let app = spawn_server().await;
     let App {
         address,
         pool,
         email_server,
-    } = spawn_server().await;
+        ..
+    } = &app;
     let client = reqwest::Client::new();
 
     let name: String = Name().fake();
@@ -67,19 +70,20 @@ async fn subscribe_with_valid_data_should_create_pending_subscription() {
         "SELECT email, name FROM subscriptions WHERE email = $1",
         email
     )
-    .fetch_one(&pool)
+    .fetch_one(pool)
     .await
     .expect_err("Should not find a subscription with this email");
 
     base_send_grid_send_endpoint_mock()
         .expect(1)
-        .mount(&email_server)
+        .mount(email_server)
         .await;
 
     let body = format!("name={name}&email={email}");
 
     // When
-    let res = post_to_subscriptions(&client, &address, body).await;
+    let res = post_to_subscriptions(&client, address, body).await;
+    app.dispatch_all_pending_emails().await;
 
     // Then
     assert_eq!(StatusCode::CREATED, res.status());
@@ -91,7 +95,7 @@ async fn subscribe_with_valid_data_should_create_pending_subscription() {
         "#,
         email
     )
-    .fetch_one(&pool)
+    .fetch_one(pool)
     .await
     .expect("Should find a subscription with this email");
 
@@ -106,25 +110,27 @@ async fn subscribe_with_valid_data_should_create_pending_subscription() {
 #[tokio::test]
 async fn subscribe_should_send_confirmation_email() {
     // Given
+    let app = spawn_server().await;
     let App {
         address,
         email_server,
         ..
-    } = spawn_server().await;
+    } = &app;
     let client = reqwest::Client::new();
 
     base_send_grid_send_endpoint_mock()
         .expect(1)
-        .mount(&email_server)
+        .mount(email_server)
         .await;
 
     // When
-    post_valid_body_to_subscriptions(&client, &address).await;
+    post_valid_body_to_subscriptions(&client, address).await;
+    app.dispatch_all_pending_emails().await;
 
     // Then
     let email_request = &email_server.received_requests().await.unwrap()[0];
     let email_body: send_grid::MailSendBody = serde_json::from_slice(&email_request.body).unwrap();
-    let email_links = links(&email_body.content[0].value);
+    let email_links = links(&email_body.content[1].value);
     assert_ge!(email_links.len(), 1);
 
     let confirmation_link = reqwest::Url::parse(email_links[0].as_str()).unwrap();
@@ -136,6 +142,33 @@ async fn subscribe_should_send_confirmation_email() {
     );
 }
 
+#[tokio::test]
+async fn the_confirmation_email_escapes_the_subscriber_name() {
+    // Given
+    let app = spawn_server().await;
+    let email: String = SafeEmail().fake();
+
+    base_send_grid_send_endpoint_mock()
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    post_to_subscriptions(
+        &reqwest::Client::new(),
+        &app.address,
+        format!("name=Tom%20%26%20Jerry&email={email}"),
+    )
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let email_body: send_grid::MailSendBody = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(email_body.content[0].value.contains("Tom & Jerry"));
+    assert!(email_body.content[1].value.contains("Tom &amp; Jerry"));
+}
+
 #[tokio::test]
 async fn subscribe_with_invalid_data_should_fail() {
     // Given
@@ -171,3 +204,313 @@ async fn subscribe_with_invalid_data_should_fail() {
         );
     }
 }
+
+#[tokio::test]
+async fn subscribe_reports_every_invalid_field_as_problem_details() {
+    // Given
+    let App { address, .. } = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    // When
+    let res = post_to_subscriptions(&client, &address, "email=not-an-email".into()).await;
+
+    // Then
+    assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    assert_eq!(
+        "application/problem+json",
+        res.headers()["Content-Type"].to_str().unwrap()
+    );
+
+    let problem: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(VALIDATION_PROBLEM_TYPE, problem["type"]);
+    assert_eq!(400, problem["status"]);
+    assert!(problem["title"].is_string());
+
+    let errors = problem["errors"].as_array().unwrap();
+    let field_codes = errors
+        .iter()
+        .map(|e| (e["field"].as_str().unwrap(), e["code"].as_str().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![("name", "blank"), ("email", "invalid")], field_codes);
+}
+
+#[tokio::test]
+async fn subscribe_reports_fields_of_the_wrong_type_as_problem_details() {
+    // Given
+    let App { address, .. } = spawn_server().await;
+
+    // When
+    let res = reqwest::Client::new()
+        .post(format!("{address}{SUBSCRIPTIONS_PATH}"))
+        .json(&serde_json::json!({"name": 1, "email": SafeEmail().fake::<String>()}))
+        .send()
+        .await
+        .unwrap();
+
+    // Then
+    assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    assert_eq!(
+        "application/problem+json",
+        res.headers()["Content-Type"].to_str().unwrap()
+    );
+
+    let problem: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(VALIDATION_PROBLEM_TYPE, problem["type"]);
+
+    let errors = problem["errors"].as_array().unwrap();
+    let field_codes = errors
+        .iter()
+        .map(|e| (e["field"].as_str().unwrap(), e["code"].as_str().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![("name", "invalid_type")], field_codes);
+}
+
+#[tokio::test]
+async fn subscribe_accepts_json_bodies() {
+    // Given
+    let App { address, pool, .. } = spawn_server().await;
+    let email: String = SafeEmail().fake();
+
+    // When
+    let res = reqwest::Client::new()
+        .post(format!("{address}{SUBSCRIPTIONS_PATH}"))
+        .json(&serde_json::json!({"name": Name().fake::<String>(), "email": email}))
+        .send()
+        .await
+        .unwrap();
+
+    // Then
+    assert_eq!(StatusCode::CREATED, res.status());
+    assert_eq!(1, count_queued_emails_to(&pool, &email).await);
+}
+
+#[tokio::test]
+async fn subscribe_rejects_unsupported_content_types() {
+    // Given
+    let App { address, .. } = spawn_server().await;
+
+    // When
+    let res = reqwest::Client::new()
+        .post(format!("{address}{SUBSCRIPTIONS_PATH}"))
+        .header("Content-Type", "text/plain")
+        .body("name=le guin&email=ursula_le_guin@gmail.com")
+        .send()
+        .await
+        .unwrap();
+
+    // Then
+    assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, res.status());
+    assert_eq!(
+        "application/problem+json",
+        res.headers()["Content-Type"].to_str().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn subscribe_reports_malformed_bodies_as_problem_details() {
+    // Given
+    let App { address, .. } = spawn_server().await;
+
+    // When
+    let res = reqwest::Client::new()
+        .post(format!("{address}{SUBSCRIPTIONS_PATH}"))
+        .header("Content-Type", "application/json")
+        .body(r#"{"name": "#)
+        .send()
+        .await
+        .unwrap();
+
+    // Then
+    assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    let problem: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(400, problem["status"]);
+    assert!(problem["detail"].is_string());
+}
+
+#[tokio::test]
+async fn subscribe_queues_the_confirmation_email_with_the_subscriber() {
+    // Given
+    let App { address, pool, .. } = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    // When
+    let (res, subscriber) = post_valid_body_to_subscriptions(&client, &address).await;
+
+    // Then
+    assert_eq!(StatusCode::CREATED, res.status());
+
+    let queued_emails = sqlx::query!("SELECT recipient FROM email_delivery_queue")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(1, queued_emails.len());
+    assert_eq!(subscriber.email.as_ref(), queued_emails[0].recipient);
+}
+
+#[tokio::test]
+async fn subscribe_does_not_persist_the_subscriber_if_the_confirmation_email_cannot_be_queued() {
+    // Given
+    let App { address, pool, .. } = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    // Sabotage the outbox
+    sqlx::query!("ALTER TABLE email_delivery_queue DROP COLUMN subject")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // When
+    let (res, subscriber) = post_valid_body_to_subscriptions(&client, &address).await;
+
+    // Then
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, res.status());
+
+    let saved_subscription = sqlx::query!(
+        "SELECT id FROM subscriptions WHERE email = $1",
+        subscriber.email.as_ref()
+    )
+    .fetch_optional(&pool)
+    .await
+    .unwrap();
+    assert!(saved_subscription.is_none());
+}
+
+async fn count_queued_emails_to(pool: &sqlx::PgPool, email: &str) -> i64 {
+    sqlx::query!(
+        r#"SELECT COUNT(*) as "count!" FROM email_delivery_queue WHERE recipient = $1"#,
+        email
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+    .count
+}
+
+async fn set_status(pool: &sqlx::PgPool, email: &str, status: SubscriptionStatus) {
+    sqlx::query!(
+        "UPDATE subscriptions SET status = $1 WHERE email = $2",
+        status as _,
+        email
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn subscribing_twice_while_pending_resends_the_confirmation_email() {
+    // Given
+    let App { address, pool, .. } = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    let name: String = Name().fake();
+    let email: String = SafeEmail().fake();
+    let body = format!("name={name}&email={email}");
+
+    let first_res = post_to_subscriptions(&client, &address, body.clone()).await;
+
+    // When
+    let second_res = post_to_subscriptions(&client, &address, body).await;
+
+    // Then
+    assert_eq!(StatusCode::CREATED, first_res.status());
+    assert_eq!(StatusCode::CREATED, second_res.status());
+    assert_eq!(
+        first_res.text().await.unwrap(),
+        second_res.text().await.unwrap()
+    );
+    assert_eq!(2, count_queued_emails_to(&pool, &email).await);
+}
+
+#[tokio::test]
+async fn subscribing_again_when_confirmed_does_not_send_an_email() {
+    // Given
+    let App { address, pool, .. } = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    let name: String = Name().fake();
+    let email: String = SafeEmail().fake();
+    let body = format!("name={name}&email={email}");
+
+    let first_res = post_to_subscriptions(&client, &address, body.clone()).await;
+    set_status(&pool, &email, SubscriptionStatus::Confirmed).await;
+
+    // When
+    let second_res = post_to_subscriptions(&client, &address, body).await;
+
+    // Then
+    assert_eq!(StatusCode::CREATED, second_res.status());
+    assert_eq!(
+        first_res.text().await.unwrap(),
+        second_res.text().await.unwrap()
+    );
+    assert_eq!(1, count_queued_emails_to(&pool, &email).await);
+
+    let saved_subscription = sqlx::query!(
+        r#"SELECT status as "status: SubscriptionStatus" FROM subscriptions WHERE email = $1"#,
+        email
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(SubscriptionStatus::Confirmed, saved_subscription.status);
+}
+
+#[tokio::test]
+async fn subscribing_again_when_unsubscribed_restarts_the_confirmation() {
+    // Given
+    let App { address, pool, .. } = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    let name: String = Name().fake();
+    let email: String = SafeEmail().fake();
+    let body = format!("name={name}&email={email}");
+
+    let first_res = post_to_subscriptions(&client, &address, body.clone()).await;
+    set_status(&pool, &email, SubscriptionStatus::Unsubscribed).await;
+
+    // When
+    let second_res = post_to_subscriptions(&client, &address, body).await;
+
+    // Then
+    assert_eq!(StatusCode::CREATED, second_res.status());
+    assert_eq!(
+        first_res.text().await.unwrap(),
+        second_res.text().await.unwrap()
+    );
+    assert_eq!(2, count_queued_emails_to(&pool, &email).await);
+
+    let saved_subscription = sqlx::query!(
+        r#"SELECT status as "status: SubscriptionStatus" FROM subscriptions WHERE email = $1"#,
+        email
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        SubscriptionStatus::PendingConfirmation,
+        saved_subscription.status
+    );
+}
+
+#[tokio::test]
+async fn concurrent_subscriptions_with_the_same_email_succeed() {
+    // Given
+    let App { address, pool, .. } = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    let name: String = Name().fake();
+    let email: String = SafeEmail().fake();
+    let body = format!("name={name}&email={email}");
+
+    // When
+    let (first_res, second_res) = tokio::join!(
+        post_to_subscriptions(&client, &address, body.clone()),
+        post_to_subscriptions(&client, &address, body)
+    );
+
+    // Then
+    assert_eq!(StatusCode::CREATED, first_res.status());
+    assert_eq!(StatusCode::CREATED, second_res.status());
+    assert_eq!(2, count_queued_emails_to(&pool, &email).await);
+}
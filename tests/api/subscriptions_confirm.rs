@@ -1,6 +1,6 @@
 use crate::subscriptions::{base_send_grid_send_endpoint_mock, post_valid_body_to_subscriptions};
 use reqwest::StatusCode;
-use zero2prod::domain::SubscriptionStatus;
+use zero2prod::domain::{SubscriptionStatus, SubscriptionToken};
 use zero2prod::email::send_grid;
 use zero2prod::startup::SUBSCRIPTIONS_CONFIRM_PATH;
 
@@ -24,25 +24,28 @@ async fn confirmation_without_token_fails() {
 #[tokio::test]
 async fn confirmation_received_from_subscribe_works() {
     // Given
+    let app = spawn_server().await;
     let App {
         address,
         email_server,
         pool,
-    } = spawn_server().await;
+        ..
+    } = &app;
 
     let client = reqwest::Client::new();
 
     base_send_grid_send_endpoint_mock()
         .expect(1)
-        .mount(&email_server)
+        .mount(email_server)
         .await;
 
     // When
-    let (_, subscriber) = post_valid_body_to_subscriptions(&client, &address).await;
+    let (_, subscriber) = post_valid_body_to_subscriptions(&client, address).await;
+    app.dispatch_all_pending_emails().await;
 
     let email_request = &email_server.received_requests().await.unwrap()[0];
     let email_body: send_grid::MailSendBody = serde_json::from_slice(&email_request.body).unwrap();
-    let email_links = links(&email_body.content[0].value);
+    let email_links = links(&email_body.content[1].value);
 
     let mut confirmation_link = reqwest::Url::parse(email_links[0].as_str()).unwrap();
     confirmation_link.set_port(address.port()).unwrap();
@@ -60,9 +63,144 @@ async fn confirmation_received_from_subscribe_works() {
         r#"SELECT status as "status: SubscriptionStatus" FROM subscriptions WHERE email = $1"#,
         subscriber.email.as_ref()
     )
-    .fetch_one(&pool)
+    .fetch_one(pool)
     .await
     .expect("Should find a subscription with this email");
 
     assert_eq!(user.status, SubscriptionStatus::Confirmed);
 }
+
+pub async fn subscribe_and_get_confirmation_link(app: &App) -> reqwest::Url {
+    let _mock_guard = base_send_grid_send_endpoint_mock()
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    post_valid_body_to_subscriptions(&reqwest::Client::new(), &app.address).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email_body: send_grid::MailSendBody = serde_json::from_slice(&email_request.body).unwrap();
+    let email_links = links(&email_body.content[1].value);
+
+    let mut confirmation_link = reqwest::Url::parse(email_links[0].as_str()).unwrap();
+    confirmation_link.set_port(app.address.port()).unwrap();
+
+    confirmation_link
+}
+
+#[tokio::test]
+async fn confirmation_with_an_unknown_token_is_unauthorized() {
+    // Given
+    let App { address, .. } = spawn_server().await;
+
+    // When
+    let res = reqwest::get(format!(
+        "{address}{SUBSCRIPTIONS_CONFIRM_PATH}?token=unknown"
+    ))
+    .await
+    .unwrap();
+
+    // Then
+    assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+}
+
+#[tokio::test]
+async fn confirmation_fails_if_there_is_a_fatal_database_error() {
+    // Given
+    let App { address, pool, .. } = spawn_server().await;
+
+    // Sabotage the database
+    sqlx::query!("ALTER TABLE subscription_tokens DROP COLUMN consumed_at")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // When
+    let res = reqwest::get(format!(
+        "{address}{SUBSCRIPTIONS_CONFIRM_PATH}?token=unknown"
+    ))
+    .await
+    .unwrap();
+
+    // Then
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, res.status());
+}
+
+#[tokio::test]
+async fn confirmation_links_can_only_be_used_once() {
+    // Given
+    let app = spawn_server().await;
+    let confirmation_link = subscribe_and_get_confirmation_link(&app).await;
+
+    reqwest::get(confirmation_link.as_str())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // When
+    let res = reqwest::get(confirmation_link.as_str()).await.unwrap();
+
+    // Then
+    assert_eq!(StatusCode::CONFLICT, res.status());
+}
+
+#[tokio::test]
+async fn expired_confirmation_links_are_rejected() {
+    // Given
+    let app = spawn_server().await;
+    let confirmation_link = subscribe_and_get_confirmation_link(&app).await;
+
+    sqlx::query!("UPDATE subscription_tokens SET expires_at = now() - interval '1 second'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    // When
+    let res = reqwest::get(confirmation_link.as_str()).await.unwrap();
+
+    // Then
+    assert_eq!(StatusCode::GONE, res.status());
+
+    let n_confirmed = sqlx::query!(
+        r#"SELECT COUNT(*) as "count!" FROM subscriptions WHERE status = $1"#,
+        SubscriptionStatus::Confirmed as _
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(0, n_confirmed);
+}
+
+#[tokio::test]
+async fn only_the_digest_of_the_token_is_stored() {
+    // Given
+    let app = spawn_server().await;
+
+    // When
+    let confirmation_link = subscribe_and_get_confirmation_link(&app).await;
+
+    // Then
+    let (_, token) = confirmation_link
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap();
+
+    let stored_token = sqlx::query!("SELECT token_hash FROM subscription_tokens")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_ne!(token, stored_token.token_hash);
+    assert_eq!(
+        SubscriptionToken::from(token.into_owned()).digest(),
+        stored_token.token_hash
+    );
+}
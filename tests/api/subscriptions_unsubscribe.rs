@@ -0,0 +1,217 @@
+use reqwest::StatusCode;
+use uuid::Uuid;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+use zero2prod::domain::{SubscriptionStatus, UnsubscribeToken};
+use zero2prod::email::send_grid;
+use zero2prod::idempotency::IDEMPOTENCY_KEY_HEADER;
+use zero2prod::startup::{NEWSLETTERS_PATH, SUBSCRIPTIONS_UNSUBSCRIBE_PATH};
+
+use crate::subscriptions::{base_send_grid_send_endpoint_mock, post_valid_body_to_subscriptions};
+use crate::utils::{links, spawn_server, App};
+
+/// Subscribes a new subscriber and returns their email and the unsubscribe link from the
+/// confirmation email.
+async fn subscribe_and_get_unsubscribe_link(app: &App) -> (String, reqwest::Url) {
+    let _mock_guard = base_send_grid_send_endpoint_mock()
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let (_, subscriber) =
+        post_valid_body_to_subscriptions(&reqwest::Client::new(), &app.address).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email_body: send_grid::MailSendBody = serde_json::from_slice(&email_request.body).unwrap();
+    let email_links = links(&email_body.content[1].value);
+
+    let mut unsubscribe_link = reqwest::Url::parse(email_links.last().unwrap().as_str()).unwrap();
+    unsubscribe_link.set_port(app.address.port()).unwrap();
+
+    (subscriber.email.as_ref().to_string(), unsubscribe_link)
+}
+
+async fn get_status(app: &App, email: &str) -> SubscriptionStatus {
+    sqlx::query!(
+        r#"SELECT status as "status: SubscriptionStatus" FROM subscriptions WHERE email = $1"#,
+        email
+    )
+    .fetch_one(&app.pool)
+    .await
+    .expect("Should find a subscription with this email")
+    .status
+}
+
+async fn post_one_click_unsubscribe(url: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(url)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .send()
+        .await
+        .unwrap_or_else(|_| panic!("Failed to POST {SUBSCRIPTIONS_UNSUBSCRIBE_PATH}"))
+}
+
+#[tokio::test]
+async fn emails_contain_an_unsubscribe_link_and_header() {
+    // Given
+    let app = spawn_server().await;
+
+    // When
+    let (_, unsubscribe_link) = subscribe_and_get_unsubscribe_link(&app).await;
+
+    // Then
+    assert_eq!(
+        format!("/{SUBSCRIPTIONS_UNSUBSCRIBE_PATH}"),
+        unsubscribe_link.path()
+    );
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let email_body: send_grid::MailSendBody = serde_json::from_slice(&email_request.body).unwrap();
+    let headers = email_body.headers.unwrap();
+    assert!(headers["List-Unsubscribe"].contains(unsubscribe_link.query().unwrap()));
+    assert_eq!(
+        "List-Unsubscribe=One-Click",
+        headers["List-Unsubscribe-Post"]
+    );
+}
+
+#[tokio::test]
+async fn visiting_the_unsubscribe_link_does_not_unsubscribe() {
+    // Given
+    let app = spawn_server().await;
+    let (email, unsubscribe_link) = subscribe_and_get_unsubscribe_link(&app).await;
+
+    // When
+    let res = reqwest::get(unsubscribe_link.as_str()).await.unwrap();
+
+    // Then
+    assert_eq!(StatusCode::OK, res.status());
+    assert!(res.text().await.unwrap().contains(r#"method="post""#));
+    assert_eq!(
+        SubscriptionStatus::PendingConfirmation,
+        get_status(&app, &email).await
+    );
+}
+
+#[tokio::test]
+async fn one_click_unsubscribe_works() {
+    // Given
+    let app = spawn_server().await;
+    let (email, unsubscribe_link) = subscribe_and_get_unsubscribe_link(&app).await;
+
+    // When
+    let res = post_one_click_unsubscribe(unsubscribe_link.as_str()).await;
+
+    // Then
+    assert_eq!(StatusCode::OK, res.status());
+    assert_eq!(
+        SubscriptionStatus::Unsubscribed,
+        get_status(&app, &email).await
+    );
+
+    let n_tokens = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM subscription_tokens
+        JOIN subscriptions ON subscriptions.id = subscription_tokens.subscription_id
+        WHERE subscriptions.email = $1
+        "#,
+        email
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(0, n_tokens);
+}
+
+#[tokio::test]
+async fn unsubscribe_with_an_invalid_token_is_rejected() {
+    // Given
+    let App { address, .. } = spawn_server().await;
+    let forged_token =
+        UnsubscribeToken::sign(&Uuid::new_v4(), &"not-the-secret".to_string().into());
+
+    for method in [reqwest::Method::GET, reqwest::Method::POST] {
+        // When
+        let res = reqwest::Client::new()
+            .request(
+                method.clone(),
+                format!("{address}{SUBSCRIPTIONS_UNSUBSCRIBE_PATH}"),
+            )
+            .query(&[("token", forged_token.as_ref())])
+            .send()
+            .await
+            .unwrap();
+
+        // Then
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status(), "{method}");
+    }
+}
+
+#[tokio::test]
+async fn unsubscribe_without_a_token_is_rejected() {
+    // Given
+    let App { address, .. } = spawn_server().await;
+
+    // When
+    let res =
+        post_one_click_unsubscribe(&format!("{address}{SUBSCRIPTIONS_UNSUBSCRIBE_PATH}")).await;
+
+    // Then
+    assert_eq!(StatusCode::BAD_REQUEST, res.status());
+}
+
+#[tokio::test]
+async fn newsletters_are_not_delivered_to_unsubscribed_subscribers() {
+    // Given
+    let app = spawn_server().await;
+    let (email, unsubscribe_link) = subscribe_and_get_unsubscribe_link(&app).await;
+
+    sqlx::query!(
+        "UPDATE subscriptions SET status = $1 WHERE email = $2",
+        SubscriptionStatus::Confirmed as _,
+        email
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+    post_one_click_unsubscribe(unsubscribe_link.as_str())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(StatusCode::OK))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // When
+    let res = reqwest::Client::new()
+        .post(format!("{}{NEWSLETTERS_PATH}", app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .header(IDEMPOTENCY_KEY_HEADER, Uuid::new_v4().to_string())
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "html": "<p>Newsletter body as HTML</p>",
+                "text": "Newsletter body as plain text",
+            }
+        }))
+        .send()
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Then
+    assert_eq!(StatusCode::ACCEPTED, res.status());
+}
@@ -0,0 +1,14 @@
+/// A client that keeps cookies between requests and does not follow redirects, like a browser
+/// whose redirects we want to assert on.
+pub fn cookie_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap()
+}
+
+pub fn assert_is_redirect_to(res: &reqwest::Response, location: &str) {
+    assert_eq!(reqwest::StatusCode::SEE_OTHER, res.status());
+    assert_eq!(location, res.headers()["Location"]);
+}
@@ -1,4 +1,4 @@
-pub fn links(str: &str) -> Vec<linkify::Link> {
+pub fn links(str: &str) -> Vec<linkify::Link<'_>> {
     linkify::LinkFinder::new()
         .links(str)
         .filter(|link| *link.kind() == linkify::LinkKind::Url)
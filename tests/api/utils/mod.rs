@@ -1,5 +1,7 @@
+pub use client::*;
 pub use extract::*;
 pub use startup::*;
 
+mod client;
 mod extract;
 mod startup;
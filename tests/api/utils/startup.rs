@@ -4,21 +4,84 @@ use std::time::Duration;
 
 use fake::faker::internet::en::SafeEmail;
 use fake::{Fake, Faker};
-use secrecy::Secret;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::postgres::PgPoolOptions;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
 use wiremock::MockServer;
 
+use zero2prod::authentication::compute_password_hash;
 use zero2prod::domain::EmailAddress;
-use zero2prod::email::EmailClient;
+use zero2prod::email::{CaptureClient, EmailSender, EmailTemplates, SendGridClient};
+use zero2prod::email_delivery_worker::{try_execute_task, ExecutionOutcome};
+use zero2prod::routes::ReadinessChecks;
 use zero2prod::settings::{AppBaseUrl, SETTINGS};
-use zero2prod::startup::run_server;
+use zero2prod::startup::{run_metrics_server, run_server, ServerSettings};
 use zero2prod::telemetry::{build_subscriber, register_global_subscriber};
 
 pub struct App {
     pub address: reqwest::Url,
+    pub metrics_address: reqwest::Url,
     pub pool: PgPool,
     pub email_server: MockServer,
+    pub email_client: SendGridClient,
+    pub test_user: TestUser,
+}
+
+impl App {
+    /// Runs the email delivery worker until there is nothing left to deliver right away.
+    pub async fn dispatch_all_pending_emails(&self) {
+        self.dispatch_all_pending_emails_with(&self.email_client)
+            .await;
+    }
+
+    /// Like `dispatch_all_pending_emails`, but stores the emails for the development mailbox.
+    pub async fn capture_all_pending_emails(&self) {
+        self.dispatch_all_pending_emails_with(&CaptureClient::new(self.pool.clone()))
+            .await;
+    }
+
+    pub async fn dispatch_all_pending_emails_with(&self, email_sender: &dyn EmailSender) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue =
+                try_execute_task(&self.pool, email_sender, &SETTINGS.email_delivery)
+                    .await
+                    .unwrap()
+            {
+                break;
+            }
+        }
+    }
+}
+
+pub struct TestUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub password: String,
+}
+
+impl TestUser {
+    pub fn generate() -> Self {
+        Self {
+            user_id: Uuid::new_v4(),
+            username: Uuid::new_v4().to_string(),
+            password: Uuid::new_v4().to_string(),
+        }
+    }
+
+    async fn store(&self, pool: &PgPool) {
+        let password_hash = compute_password_hash(Secret::new(self.password.clone())).unwrap();
+
+        sqlx::query!(
+            "INSERT INTO users (id, username, password_hash) VALUES ($1, $2, $3)",
+            self.user_id,
+            self.username,
+            password_hash.expose_secret(),
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to store test user");
+    }
 }
 
 static INIT_TELEMETRY: Once = Once::new();
@@ -37,23 +100,65 @@ pub async fn spawn_server() -> App {
         reqwest::Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
 
     let email_server = MockServer::start().await;
-    let email_client = EmailClient::new(
+    let email_client = SendGridClient::new(
         email_server.uri(),
         Secret::new(Faker.fake()),
         EmailAddress::parse(SafeEmail().fake()).unwrap(),
-        Duration::from_millis(200),
+        Duration::from_secs(2),
         true,
     );
 
-    let server = run_server(listener, &pool, email_client, AppBaseUrl(address.clone()))
-        .expect("Failed to start server");
+    let server = run_server(
+        listener,
+        &pool,
+        ServerSettings {
+            app_base_url: AppBaseUrl(address.clone()),
+            cookie_signing_key: SETTINGS
+                .app
+                .cookie_signing_key()
+                .expect("Invalid cookie signing key"),
+            session_expiry: Duration::from_secs(SETTINGS.app.session_expiry_secs),
+            hmac_secret: SETTINGS.app.hmac_secret().expect("Invalid HMAC secret"),
+            subscription_token_expiry: Duration::from_secs(
+                SETTINGS.app.subscription_token_expiry_secs,
+            ),
+            email_templates: EmailTemplates::load(&SETTINGS.email_templates)
+                .expect("Failed to load email templates"),
+            readiness_checks: ReadinessChecks {
+                timeout: Duration::from_secs(1),
+                max_connections: SETTINGS.database.max_connections,
+                email_provider: Some((
+                    email_server.address().ip().to_string(),
+                    email_server.address().port(),
+                )),
+            },
+            enable_dev_mailbox: true,
+        },
+    )
+    .expect("Failed to start server");
 
     tokio::spawn(server);
 
+    let metrics_listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind random port");
+    let metrics_address = reqwest::Url::parse(&format!(
+        "http://{}",
+        metrics_listener.local_addr().unwrap()
+    ))
+    .unwrap();
+    let metrics_server =
+        run_metrics_server(metrics_listener, &pool).expect("Failed to start metrics server");
+    tokio::spawn(metrics_server);
+
+    let test_user = TestUser::generate();
+    test_user.store(&pool).await;
+
     App {
         address,
+        metrics_address,
         pool,
         email_server,
+        email_client,
+        test_user,
     }
 }
 
@@ -67,7 +172,9 @@ pub async fn create_random_database() -> PgPool {
         .await
         .expect("Failed to create database");
 
-    let pool = PgPool::connect_with(SETTINGS.database.without_db().database(&db_name))
+    let pool = PgPoolOptions::new()
+        .max_connections(SETTINGS.database.max_connections)
+        .connect_with(SETTINGS.database.without_db().database(&db_name))
         .await
         .expect("Failed to connect to Postgres");
 